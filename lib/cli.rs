@@ -0,0 +1,175 @@
+use {
+    crate::commands::{
+        graph,
+        play,
+        routes,
+        transcript,
+        validate,
+        vars,
+    },
+    nds_novel::{
+        error::{
+            GlobalStoreError,
+            InterpreterError,
+            LoadScriptError,
+            NovelLoadError,
+            PlaythroughError,
+            RuntimeError,
+        },
+        parser::error::RenderError,
+    },
+    std::io::{
+        self,
+        BufRead,
+        Write,
+    },
+    thiserror::Error,
+};
+
+pub const USAGE: &str = "\
+Usage: vrs <command> [arguments]
+
+Commands:
+    validate <novel>            Check scripts and resources of the novel
+    play [--no-delay] <novel>   Play the novel in the console
+    graph [--mermaid] <novel>   Print control flow graph in the DOT or
+                                Mermaid format
+    vars <novel>                List variables with their reads and writes
+    transcript [--choices <first|last|n,...>] [--seed <n>] <novel>
+                                Play the novel without the player and
+                                print every event
+    routes [--max-states <n>] <novel>
+                                List endings with the choices leading to
+                                them and options which are never picked";
+
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("{0}\n\n{USAGE}")]
+    Usage(String),
+
+    #[error("Failed to load novel: {0}")]
+    Load(#[from] NovelLoadError),
+
+    #[error("Failed to load script: {0}")]
+    Script(#[from] LoadScriptError),
+
+    #[error("Script execution failed: {0}")]
+    Interpreter(#[from] InterpreterError),
+
+    #[error("{0}")]
+    Runtime(#[from] RuntimeError),
+
+    #[error("Failed to render text: {0}")]
+    Render(#[from] RenderError),
+
+    #[error("Playthrough failed: {0}")]
+    Playthrough(#[from] PlaythroughError),
+
+    #[error("Failed to access global variables: {0}")]
+    Globals(#[from] GlobalStoreError),
+
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Run command specified by `args` (without the program
+/// name). Returns `false` if the command has found problems
+pub fn run(
+    args: &[String],
+    input: &mut impl BufRead,
+    out: &mut impl Write,
+) -> Result<bool, CliError> {
+    let (command, args) = args.split_first().ok_or_else(|| {
+        CliError::Usage("No command specified".to_owned())
+    })?;
+
+    match command.as_str() {
+        "validate" => validate::run(single_argument(args, "novel")?, out),
+        "play" => {
+            let (no_delay, args) = flag(args, "--no-delay");
+            play::run(
+                single_argument(args, "novel")?,
+                !no_delay,
+                input,
+                out,
+            )
+        }
+        "graph" => {
+            let (mermaid, args) = flag(args, "--mermaid");
+            graph::run(single_argument(args, "novel")?, mermaid, out)
+        }
+        "transcript" => {
+            let ([choices, seed], args) =
+                options(args, ["--choices", "--seed"])?;
+            transcript::run(
+                single_argument(args, "novel")?,
+                choices,
+                seed,
+                out,
+            )
+        }
+        "routes" => {
+            let ([max_states], args) = options(args, ["--max-states"])?;
+            routes::run(single_argument(args, "novel")?, max_states, out)
+        }
+        "vars" => vars::run(single_argument(args, "novel")?, out),
+        "help" | "-h" | "--help" => {
+            writeln!(out, "{USAGE}")?;
+            Ok(true)
+        }
+
+        command => {
+            Err(CliError::Usage(format!("Unknown command: {command}")))
+        }
+    }
+}
+
+/// Strip the leading `flag` from `args`, returns whether
+/// it was present
+pub(crate) fn flag<'a>(
+    args: &'a [String],
+    flag: &str,
+) -> (bool, &'a [String]) {
+    match args {
+        [first, rest @ ..] if first == flag => (true, rest),
+        args => (false, args),
+    }
+}
+
+/// Strip the leading `--name value` options, returns their
+/// values in the order of `names`
+pub(crate) fn options<'a, const N: usize>(
+    mut args: &'a [String],
+    names: [&str; N],
+) -> Result<([Option<&'a str>; N], &'a [String]), CliError> {
+    let mut values = [None; N];
+    while let [name, rest @ ..] = args {
+        let Some(index) = names.iter().position(|n| n == name) else {
+            break;
+        };
+        let [value, rest @ ..] = rest else {
+            return Err(CliError::Usage(format!(
+                "Missing value of {name}"
+            )));
+        };
+
+        values[index] = Some(value.as_str());
+        args = rest;
+    }
+
+    Ok((values, args))
+}
+
+/// Get the only positional argument named `name`
+pub(crate) fn single_argument<'a>(
+    args: &'a [String],
+    name: &str,
+) -> Result<&'a str, CliError> {
+    match args {
+        [arg] => Ok(arg),
+        [] => Err(CliError::Usage(format!("Missing <{name}> argument"))),
+        [_, extra, ..] => {
+            Err(CliError::Usage(format!("Unexpected argument: {extra}")))
+        }
+    }
+}
@@ -0,0 +1,30 @@
+use {
+    crate::cli::CliError,
+    nds_novel::{
+        graph::ControlFlowGraph,
+        novel::Novel,
+    },
+    std::io::Write,
+};
+
+/// Print control flow graph of the novel in the Graphviz
+/// or Mermaid format
+pub fn run(
+    novel: &str,
+    mermaid: bool,
+    out: &mut impl Write,
+) -> Result<bool, CliError> {
+    let novel = Novel::try_load(novel)?;
+    let graph = ControlFlowGraph::build(&novel.index);
+
+    out.write_all(
+        if mermaid {
+            graph.to_mermaid()
+        } else {
+            graph.to_dot()
+        }
+        .as_bytes(),
+    )?;
+
+    Ok(true)
+}
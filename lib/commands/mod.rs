@@ -0,0 +1,6 @@
+pub mod graph;
+pub mod play;
+pub mod routes;
+pub mod transcript;
+pub mod validate;
+pub mod vars;
@@ -0,0 +1,274 @@
+use {
+    crate::cli::CliError,
+    nds_novel::{
+        global::{
+            self,
+            GlobalStore,
+        },
+        interpreter::Event,
+        novel::Novel,
+        parser::{
+            command::{
+                ClearTextType,
+                MusicFile,
+                SoundLooping,
+            },
+            text::{
+                MissingVariable,
+                Text,
+            },
+        },
+        runtime::Runtime,
+    },
+    std::{
+        fmt::Display,
+        io::{
+            BufRead,
+            Write,
+        },
+        thread,
+        time::Duration,
+    },
+};
+
+/// Frames per second of the original engine, used to
+/// convert `delay` commands
+const FRAMES_PER_SECOND: u64 = 60;
+
+/// Line-based console frontend for the novel runtime
+pub struct Player<'a, R, W> {
+    runtime: Runtime<'a>,
+
+    /// Store which receives global variables once they
+    /// are changed
+    globals: Option<GlobalStore>,
+
+    input: R,
+    out: W,
+
+    /// Whether `delay` commands actually sleep
+    pub delays: bool,
+
+    /// How the variables of the text and the choices which
+    /// are not set are rendered
+    pub missing_variables: MissingVariable,
+}
+
+/// What the player has answered on the prompt
+enum Answer {
+    Line(String),
+    Quit,
+}
+
+impl<'a, R: BufRead, W: Write> Player<'a, R, W> {
+    /// Start playing the novel from the main script
+    pub fn new(
+        novel: &'a Novel,
+        input: R,
+        out: W,
+    ) -> Result<Self, CliError> {
+        Ok(Self {
+            runtime: Runtime::new(novel)?,
+            globals: None,
+            input,
+            out,
+            delays: true,
+            missing_variables: MissingVariable::default(),
+        })
+    }
+
+    /// Persist global variables in the `globals` store,
+    /// previously stored values are loaded
+    pub fn with_globals(mut self, globals: GlobalStore) -> Self {
+        globals.load_into(self.runtime.interpreter_mut().variables_mut());
+        self.globals = Some(globals);
+        self
+    }
+
+    /// Play until the end of the novel or until the player
+    /// quits
+    pub fn run(&mut self) -> Result<(), CliError> {
+        self.runtime
+            .interpreter_mut()
+            .set_missing_variables(self.missing_variables);
+
+        loop {
+            let event = self.runtime.step()?;
+            if let Some(globals) = &mut self.globals {
+                globals.update(self.runtime.interpreter().variables())?;
+            }
+
+            match event {
+                Event::Text(text) => {
+                    let click_to_advance = self.print_text(&text)?;
+                    if click_to_advance && !self.wait_for_click()? {
+                        return Ok(());
+                    }
+                }
+                Event::ClearText(ty) => self.status(match ty {
+                    ClearTextType::FillBottomScreen => "clear text",
+                    ClearTextType::TextBufferInclHistory => {
+                        "clear text and history"
+                    }
+                })?,
+
+                Event::Background { file, .. } => self.status(
+                    format_args!("background: {}", file.display()),
+                )?,
+                Event::Foreground { file, coordinates } => {
+                    self.status(format_args!(
+                        "foreground: {} at {}, {}",
+                        file.display(),
+                        coordinates.0,
+                        coordinates.1
+                    ))?
+                }
+                Event::Sound(looping) => match looping {
+                    SoundLooping::Infinite { file } => self.status(
+                        format_args!("sound: {} (looped)", file.display()),
+                    )?,
+                    SoundLooping::Count { file, count } => {
+                        self.status(format_args!(
+                            "sound: {} ({count} times)",
+                            file.display()
+                        ))?
+                    }
+                    SoundLooping::StopCurrentlyPlaying => {
+                        self.status("sound stopped")?
+                    }
+                },
+                Event::Music(MusicFile::Path(file)) => {
+                    self.status(format_args!("music: {}", file.display()))?
+                }
+                Event::Music(MusicFile::StopPlaying) => {
+                    self.status("music stopped")?
+                }
+
+                Event::Choice { options } => {
+                    let Some(option) = self.ask_choice(&options)? else {
+                        return Ok(());
+                    };
+                    self.runtime.choose(option)?;
+                }
+                Event::Delay { frames } => {
+                    self.out.flush()?;
+                    if self.delays {
+                        thread::sleep(Duration::from_millis(
+                            u64::from(frames) * 1000 / FRAMES_PER_SECOND,
+                        ));
+                    }
+                }
+                // followed by the runtime
+                Event::Jump { .. } => {}
+
+                Event::Finished => {
+                    self.status("end")?;
+                    return Ok(());
+                }
+            }
+        }
+    }
+
+    /// Print text with its colors, returns whether the text
+    /// waits for the click
+    fn print_text(&mut self, text: &Text) -> Result<bool, CliError> {
+        let click_to_advance = match text {
+            Text::BlankLine { click_to_advance } => {
+                writeln!(self.out)?;
+                return Ok(*click_to_advance);
+            }
+            Text::Spans {
+                click_to_advance, ..
+            } => *click_to_advance,
+        };
+
+        let variables = self.runtime.interpreter().variables();
+        for span in text.render(variables, self.missing_variables)? {
+            write!(self.out, "\x1b[0")?;
+            for parameter in span.style.parameters() {
+                write!(self.out, ";{parameter}")?;
+            }
+            write!(self.out, "m{}", span.text)?;
+        }
+        writeln!(self.out, "\x1b[0m")?;
+
+        Ok(click_to_advance)
+    }
+
+    /// Print presentation change which has no text
+    /// representation
+    fn status(&mut self, status: impl Display) -> Result<(), CliError> {
+        writeln!(self.out, "\x1b[2m[{status}]\x1b[0m")?;
+        Ok(())
+    }
+
+    /// Wait for Enter, returns `false` if the player quits
+    fn wait_for_click(&mut self) -> Result<bool, CliError> {
+        Ok(matches!(self.prompt("")?, Answer::Line(..)))
+    }
+
+    /// List choice options and ask for one of them until
+    /// the valid one is entered. Returns 0-based index of
+    /// the option or `None` if the player quits
+    fn ask_choice(
+        &mut self,
+        options: &[String],
+    ) -> Result<Option<usize>, CliError> {
+        for (index, option) in options.iter().enumerate() {
+            writeln!(self.out, "  {}) {option}", index + 1)?;
+        }
+
+        loop {
+            let Answer::Line(line) = self.prompt("> ")? else {
+                return Ok(None);
+            };
+
+            match line.parse::<usize>() {
+                Ok(n) if (1..=options.len()).contains(&n) => {
+                    return Ok(Some(n - 1))
+                }
+                _ => writeln!(
+                    self.out,
+                    "Enter a number from 1 to {}",
+                    options.len()
+                )?,
+            }
+        }
+    }
+
+    /// Read the line, `q` or the end of input quits
+    fn prompt(&mut self, prompt: &str) -> Result<Answer, CliError> {
+        write!(self.out, "{prompt}")?;
+        self.out.flush()?;
+
+        let mut line = String::new();
+        if self.input.read_line(&mut line)? == 0 {
+            return Ok(Answer::Quit);
+        }
+
+        Ok(match line.trim() {
+            "q" => Answer::Quit,
+            line => Answer::Line(line.to_owned()),
+        })
+    }
+}
+
+/// Play the novel in the console, text is advanced with
+/// Enter and choices are selected by their number. Global
+/// variables are kept in the novel save directory
+pub fn run(
+    novel: &str,
+    delays: bool,
+    input: impl BufRead,
+    out: impl Write,
+) -> Result<bool, CliError> {
+    let globals = global::save_directory(novel);
+    let novel = Novel::try_load(novel)?;
+    let globals = GlobalStore::open(globals)?;
+    let mut player =
+        Player::new(&novel, input, out)?.with_globals(globals);
+    player.delays = delays;
+    player.run()?;
+
+    Ok(true)
+}
@@ -0,0 +1,90 @@
+use {
+    crate::cli::CliError,
+    nds_novel::{
+        novel::Novel,
+        routes::{
+            ChoicePath,
+            Explorer,
+        },
+    },
+    std::{
+        io::Write,
+        path::Path,
+    },
+};
+
+/// Print every ending of the novel with the choice paths to
+/// it and the options no path picks. Returns `false` if
+/// some path fails
+pub fn run(
+    novel: &str,
+    max_states: Option<&str>,
+    out: &mut impl Write,
+) -> Result<bool, CliError> {
+    let novel = Novel::try_load(novel)?;
+    let mut explorer = Explorer::new(&novel);
+    if let Some(max_states) = max_states {
+        explorer.max_states = max_states.parse().map_err(|_| {
+            CliError::Usage(format!("Invalid state limit: {max_states}"))
+        })?;
+    }
+    let exploration = explorer.explore()?;
+
+    for ending in &exploration.endings {
+        write!(
+            out,
+            "ending {}",
+            Path::new("script").join(&ending.file).display()
+        )?;
+        if let Some(label) = &ending.label {
+            write!(out, " at label {label}")?;
+        }
+        writeln!(out)?;
+
+        for path in &ending.paths {
+            writeln!(out, "    {}", format_path(path))?;
+        }
+    }
+
+    for option in &exploration.unreached {
+        writeln!(out, "never picked: {option}")?;
+    }
+    for failure in &exploration.failures {
+        writeln!(
+            out,
+            "failed after {}: {}",
+            format_path(&failure.path),
+            failure.error
+        )?;
+    }
+    if exploration.state_limit_reached {
+        writeln!(
+            out,
+            "exploration stopped at the limit of {} states, results are \
+             incomplete",
+            explorer.max_states
+        )?;
+    }
+    if exploration.event_limit_reached {
+        writeln!(
+            out,
+            "some paths ran {} events without a choice and were \
+             abandoned, results are incomplete",
+            explorer.max_events
+        )?;
+    }
+
+    Ok(exploration.failures.is_empty())
+}
+
+/// Format as `<number>) <text> -> ...`
+fn format_path(path: &ChoicePath) -> String {
+    if path.is_empty() {
+        return "no choices".to_owned();
+    }
+
+    path.iter()
+        .map(|step| format!("{}) {}", step.option + 1, step.text))
+        .collect::<Vec<_>>()
+        .join(" -> ")
+}
@@ -0,0 +1,54 @@
+use {
+    crate::cli::CliError,
+    nds_novel::{
+        novel::Novel,
+        playthrough::{
+            ChoicePolicy,
+            Playthrough,
+        },
+        random::XorShift,
+    },
+    std::io::Write,
+};
+
+/// Play the novel without the player and print every event.
+/// Choices are answered by the `choices` policy: `first`,
+/// `last` or comma-separated 1-based option numbers
+pub fn run(
+    novel: &str,
+    choices: Option<&str>,
+    seed: Option<&str>,
+    out: &mut impl Write,
+) -> Result<bool, CliError> {
+    let policy = match choices.unwrap_or("first") {
+        "first" => ChoicePolicy::First,
+        "last" => ChoicePolicy::Last,
+        choices => ChoicePolicy::Scripted(
+            choices
+                .split(',')
+                .map(|choice| match choice.trim().parse::<usize>() {
+                    Ok(n) if n > 0 => Ok(n - 1),
+                    _ => Err(CliError::Usage(format!(
+                        "Invalid choice number: {choice}"
+                    ))),
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+    };
+    let seed = match seed {
+        Some(seed) => seed.parse().map_err(|_| {
+            CliError::Usage(format!("Invalid seed: {seed}"))
+        })?,
+        None => 0,
+    };
+
+    let novel = Novel::try_load(novel)?;
+    let mut playthrough =
+        Playthrough::with_random(&novel, policy, XorShift::seeded(seed))?;
+    let result = playthrough.run();
+
+    write!(out, "{}", playthrough.transcript())?;
+    result?;
+
+    Ok(true)
+}
@@ -0,0 +1,33 @@
+use {
+    crate::cli::CliError,
+    nds_novel::{
+        novel::Novel,
+        parser::error::Severity,
+        validate,
+    },
+    std::io::Write,
+};
+
+/// Print every finding and the summary, fails if any of the
+/// findings is an error
+pub fn run(novel: &str, out: &mut impl Write) -> Result<bool, CliError> {
+    let novel = Novel::try_load(novel)?;
+    let findings = validate::validate(&novel);
+
+    for finding in &findings {
+        writeln!(out, "{finding}")?;
+    }
+
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    writeln!(
+        out,
+        "{} scripts checked: {errors} errors, {} warnings",
+        novel.index.scripts().count(),
+        findings.len() - errors
+    )?;
+
+    Ok(errors == 0)
+}
@@ -0,0 +1,50 @@
+use {
+    crate::cli::CliError,
+    nds_novel::{
+        novel::Novel,
+        variables::{
+            AccessKind,
+            VariableAnalysis,
+        },
+    },
+    std::io::Write,
+};
+
+/// Print every variable of the novel with its accesses.
+/// Suspicious variables are only reported, checks which
+/// fail belong to `validate`
+pub fn run(novel: &str, out: &mut impl Write) -> Result<bool, CliError> {
+    let novel = Novel::try_load(novel)?;
+    let analysis = VariableAnalysis::analyze(&novel.index);
+
+    for (name, usage) in &analysis.variables {
+        writeln!(
+            out,
+            "{name}: {}, {} writes, {} reads",
+            usage.storage,
+            usage.writes().count(),
+            usage.reads().count()
+        )?;
+
+        for access in &usage.accesses {
+            let kind = match access.kind {
+                AccessKind::Read => "read",
+                AccessKind::Write => "write",
+            };
+            if usage.read_before_write.contains(access) {
+                writeln!(out, "    {access}: {kind}, may be unset")?;
+            } else {
+                writeln!(out, "    {access}: {kind}")?;
+            }
+        }
+    }
+
+    for (a, b) in &analysis.typo_suspects {
+        writeln!(
+            out,
+            "possible typo: {a} and {b} differ by one character"
+        )?;
+    }
+
+    Ok(true)
+}
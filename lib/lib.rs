@@ -1,2 +1,5 @@
+pub mod cli;
+pub mod commands;
+
 #[cfg(test)]
 mod tests;
@@ -0,0 +1,240 @@
+use {
+    crate::cli::{
+        self,
+        CliError,
+    },
+    std::{
+        env,
+        fs,
+        path::{
+            Path,
+            PathBuf,
+        },
+    },
+};
+
+/// Write minimal novel with the `main.scr` script into the
+/// fresh directory
+fn write_novel(name: &str, script: &str) -> PathBuf {
+    let root =
+        env::temp_dir().join(format!("vrs-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&root);
+
+    for directory in ["background", "foreground", "sound", "script"] {
+        fs::create_dir_all(root.join(directory)).unwrap();
+    }
+    for (file, contents) in [
+        ("info.txt", "title=Test"),
+        ("img.ini", "width=256\nheight=192"),
+        ("icon.png", ""),
+        ("icon-high.png", ""),
+        ("thumbnail.png", ""),
+        ("background/bg.jpg", ""),
+        ("script/main.scr", script),
+    ] {
+        fs::write(root.join(file), contents).unwrap();
+    }
+
+    root
+}
+
+fn run(args: &[&str]) -> (Result<bool, CliError>, String) {
+    run_with_input(args, "")
+}
+
+fn run_with_input(
+    args: &[&str],
+    input: &str,
+) -> (Result<bool, CliError>, String) {
+    let args: Vec<String> = args.iter().map(|&a| a.to_owned()).collect();
+    let mut out = Vec::new();
+    let result = cli::run(&args, &mut input.as_bytes(), &mut out);
+
+    (result, String::from_utf8(out).unwrap())
+}
+
+fn path_arg(path: &Path) -> &str {
+    path.to_str().unwrap()
+}
+
+#[test]
+fn test_validate_clean() {
+    let root =
+        write_novel("validate-clean", "bgload bg.jpg\nlabel a\ngoto a");
+    let (result, out) = run(&["validate", path_arg(&root)]);
+
+    assert!(result.unwrap());
+    assert_eq!(out, "1 scripts checked: 0 errors, 0 warnings\n");
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_validate_errors() {
+    let root = write_novel("validate-errors", "bgload missing.jpg\nfi");
+    let (result, out) = run(&["validate", path_arg(&root)]);
+
+    assert!(!result.unwrap());
+    assert_eq!(
+        out,
+        "script/main.scr:1:1: error: background missing.jpg was not \
+         found\nscript/main.scr:2:1: error: fi without matching if\n1 \
+         scripts checked: 2 errors, 0 warnings\n"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_usage_errors() {
+    assert!(matches!(run(&[]).0, Err(CliError::Usage(..))));
+    assert!(matches!(run(&["validate"]).0, Err(CliError::Usage(..))));
+    assert!(matches!(run(&["unknown"]).0, Err(CliError::Usage(..))));
+    assert!(matches!(
+        run(&["validate", "/nonexistent/novel"]).0,
+        Err(CliError::Load(..))
+    ));
+}
+
+#[test]
+fn test_play() {
+    let root = write_novel(
+        "play",
+        "bgload bg.jpg\ntext Hello, \\x1b[31;1mworld\nchoice \
+         Left|Right\nif selected == 2\ntext @Right $selected\nfi\ndelay \
+         60\nmusic ~\ntext !",
+    );
+    let (result, out) = run_with_input(
+        &["play", "--no-delay", path_arg(&root)],
+        "\n0\n2\n\n",
+    );
+
+    assert!(result.unwrap());
+    assert_eq!(
+        out.lines().collect::<Vec<_>>(),
+        [
+            "\x1b[2m[background: bg.jpg]\x1b[0m",
+            "\x1b[0mHello, \x1b[0;31;1mworld\x1b[0m",
+            "  1) Left",
+            "  2) Right",
+            "> Enter a number from 1 to 2",
+            "> \x1b[0mRight 2\x1b[0m",
+            "\x1b[2m[music stopped]\x1b[0m",
+            "",
+            "\x1b[2m[end]\x1b[0m",
+        ]
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_play_quit() {
+    let root = write_novel("play-quit", "text first\ntext second");
+    let (result, out) = run_with_input(&["play", path_arg(&root)], "q\n");
+
+    assert!(result.unwrap());
+    assert_eq!(out, "\x1b[0mfirst\x1b[0m\n");
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_play_keeps_globals() {
+    let root = write_novel(
+        "play-globals",
+        "if seen == 1\ntext @again\nfi\ngsetvar seen = 1\ntext @done",
+    );
+    let play = || run(&["play", path_arg(&root)]);
+
+    assert!(!play().1.contains("again"));
+    assert!(play().1.contains("again"));
+    assert!(root.join("save").join("global.vars").exists());
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_graph() {
+    let root = write_novel("graph", "label a\ngoto a");
+    let (result, dot) = run(&["graph", path_arg(&root)]);
+    assert!(result.unwrap());
+    assert!(dot.contains("b0 -> b0 [style=dashed];"));
+
+    let (result, mermaid) = run(&["graph", "--mermaid", path_arg(&root)]);
+    assert!(result.unwrap());
+    assert!(mermaid.starts_with("flowchart TD\n"));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_vars() {
+    let root = write_novel("vars", "setvar a = 1\nif a == 1\nfi\ntext $b");
+    let (result, output) = run(&["vars", path_arg(&root)]);
+    assert!(result.unwrap());
+    assert_eq!(
+        output,
+        "a: local, 1 writes, 1 reads\n    script/main.scr:1:1: write\n    \
+         script/main.scr:2:1: read\nb: never written, 0 writes, 1 reads\n    \
+         script/main.scr:4:1: read, may be unset\n"
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_transcript() {
+    let root = write_novel("transcript", "choice a|b\ntext $selected");
+    let (result, output) =
+        run(&["transcript", "--choices", "2", path_arg(&root)]);
+    assert!(result.unwrap());
+    assert_eq!(
+        output,
+        "script/main.scr:1:1: choice a|b -> 2\nscript/main.scr:2:1: text \
+         2\nscript/main.scr: end\n"
+    );
+
+    let (result, output) =
+        run(&["transcript", "--choices", "2,1", path_arg(&root)]);
+    assert!(result.is_ok());
+    assert!(output.starts_with("script/main.scr:1:1: choice a|b -> 2\n"));
+
+    let (result, _) =
+        run(&["transcript", "--choices", "0", path_arg(&root)]);
+    assert!(matches!(result, Err(CliError::Usage(..))));
+
+    let (result, output) = run(&[
+        "transcript",
+        "--seed",
+        "1",
+        "--choices",
+        "3",
+        path_arg(&root),
+    ]);
+    assert!(matches!(result, Err(CliError::Playthrough(..))));
+    assert!(output.is_empty());
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_routes() {
+    let root = write_novel(
+        "routes",
+        "choice a|b\nif selected == 1\ngoto end\nfi\njump \
+         missing.scr\nlabel end\ntext end",
+    );
+    let (result, output) = run(&["routes", path_arg(&root)]);
+    assert!(!result.unwrap());
+    assert!(output.starts_with(
+        "ending script/main.scr at label end\n    1) a\nfailed after 2) \
+         b: script/main.scr:5:1: failed to load jump target missing.scr: "
+    ));
+
+    let (result, _) =
+        run(&["routes", "--max-states", "x", path_arg(&root)]);
+    assert!(matches!(result, Err(CliError::Usage(..))));
+
+    fs::remove_dir_all(root).unwrap();
+}
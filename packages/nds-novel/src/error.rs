@@ -1,6 +1,17 @@
 use {
-    nds_parser::error::ParseError,
-    std::io,
+    crate::runtime::CallSite,
+    nds_parser::{
+        command::VariableModifier,
+        error::{
+            LocatedParseError,
+            RenderError,
+            TextParseError,
+        },
+    },
+    std::{
+        io,
+        path::PathBuf,
+    },
     thiserror::Error,
 };
 
@@ -15,8 +26,12 @@ pub enum LoadScriptError {
     #[error("I/O error: {0}")]
     Io(#[from] io::Error),
 
-    #[error("Parse error: {0}")]
-    Parse(#[from] ParseError),
+    #[error("{}:{error}", path.display())]
+    Parse {
+        /// Script path relative to the novel root
+        path: PathBuf,
+        error: LocatedParseError,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
@@ -25,6 +40,57 @@ pub enum JumpToLabelError {
     LabelNotFound,
 }
 
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum InterpreterError {
+    #[error("Label {label} was not found")]
+    LabelNotFound { label: String },
+
+    #[error("Interpreter is waiting for the choice")]
+    ChoicePending,
+
+    #[error("There is no pending choice")]
+    NoChoicePending,
+
+    #[error("Choice option {option} is out of range ({count} options)")]
+    ChoiceOutOfRange { option: usize, count: usize },
+
+    #[error("Variable modifier {0:?} is not supported by setvar")]
+    UnsupportedModifier(VariableModifier),
+
+    #[error("Can't execute invalid command: {0}")]
+    InvalidCommand(String),
+
+    #[error("Can't show the choice option: {0}")]
+    Render(#[from] RenderError),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum InfoParseError {
+    #[error("info.txt does not contain title")]
+    NoTitle,
+
+    #[error("line {line}: expected key=value, found {text}")]
+    MalformedLine { line: usize, text: String },
+
+    #[error("line {line}: key {key} is specified twice")]
+    DuplicateKey { line: usize, key: String },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ImgIniError {
+    #[error("required key {0} is missing")]
+    MissingKey(&'static str),
+
+    #[error("invalid value of the {key} key: {value}")]
+    InvalidValue { key: String, value: String },
+
+    #[error("line {line}: expected key=value, found {text}")]
+    MalformedLine { line: usize, text: String },
+
+    #[error("line {line}: key {key} is specified twice")]
+    DuplicateKey { line: usize, key: String },
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Error)]
 pub enum NovelLoadError {
     #[error("background directory was not found")]
@@ -39,6 +105,9 @@ pub enum NovelLoadError {
     #[error("script directory was not found")]
     NoScriptDirectory,
 
+    #[error("Failed to read scripts from the script directory")]
+    InvalidScriptDirectory,
+
     #[error("icon-high.png was not found")]
     NoHighResolutionIcon,
 
@@ -48,9 +117,137 @@ pub enum NovelLoadError {
     #[error("thumbnail.png was not found")]
     NoThumbnail,
 
-    #[error("info.txt does not contain title")]
-    NoTitle,
+    #[error("info.txt was not found")]
+    NoInfo,
+
+    #[error("Invalid info.txt: {0}")]
+    Info(#[from] InfoParseError),
+
+    #[error("img.ini was not found")]
+    NoImgIni,
+
+    #[error("Invalid img.ini: {0}")]
+    Img(#[from] ImgIniError),
+
+    #[error("Failed to open archive {}", .0.display())]
+    InvalidArchive(PathBuf),
+}
+
+#[derive(Debug, Clone, Error)]
+pub enum SaveParseError {
+    #[error("not a save file")]
+    NoHeader,
+
+    #[error("unsupported save format version {0}")]
+    UnsupportedVersion(String),
+
+    #[error("required key {0} is missing")]
+    MissingKey(&'static str),
+
+    #[error("line {line}: malformed entry {text}")]
+    MalformedLine { line: usize, text: String },
+
+    #[error("line {line}: invalid history text: {error}")]
+    Text { line: usize, error: TextParseError },
+}
+
+#[derive(Debug, Error)]
+pub enum RestoreSaveError {
+    #[error("Failed to load saved script: {0}")]
+    Script(#[from] LoadScriptError),
+
+    #[error(
+        "Saved position {cursor} is outside of the script with {len} \
+         commands"
+    )]
+    CursorOutOfRange { cursor: usize, len: usize },
+}
+
+#[derive(Debug, Error)]
+pub enum GlobalStoreError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+
+    #[error("not a global variables file")]
+    NoHeader,
+
+    #[error("unsupported global variables format version {0}")]
+    UnsupportedVersion(String),
+
+    #[error("line {line}: expected name and value, found {text}")]
+    MalformedLine { line: usize, text: String },
+}
+
+#[derive(Debug, Error)]
+pub enum VndsSaveError {
+    #[error("malformed XML at byte {offset}")]
+    Xml { offset: usize },
+
+    #[error("expected <{expected}> root element, found <{found}>")]
+    UnexpectedRoot {
+        expected: &'static str,
+        found: String,
+    },
+
+    #[error("required element <{0}> is missing")]
+    MissingElement(&'static str),
+
+    #[error("invalid value of {name}: {value}")]
+    InvalidValue { name: String, value: String },
+
+    #[error("Failed to load saved script: {0}")]
+    Script(#[from] LoadScriptError),
+}
+
+#[derive(Debug, Error)]
+pub enum RuntimeError {
+    #[error("{call_site}: failed to load jump target {}: {error}", file.display())]
+    LoadScript {
+        call_site: CallSite,
+        file: PathBuf,
+        error: Box<LoadScriptError>,
+    },
+
+    #[error("{call_site}: label {label} is not defined in {}", file.display())]
+    LabelNotFound {
+        call_site: CallSite,
+        file: PathBuf,
+        label: String,
+    },
+
+    #[error("Script execution failed: {0}")]
+    Interpreter(#[from] InterpreterError),
+}
+
+#[derive(Debug, Error)]
+pub enum PlaythroughError {
+    #[error("{0}")]
+    Runtime(#[from] RuntimeError),
+
+    #[error("{call_site}: no answer for the choice {}", number + 1)]
+    ChoicesExhausted {
+        call_site: CallSite,
+
+        /// 0-based number of the choice in the playthrough
+        number: usize,
+    },
+
+    #[error(
+        "{call_site}: option {} is out of range, choice has {count} options",
+        option + 1
+    )]
+    ChoiceOutOfRange {
+        call_site: CallSite,
+        option: usize,
+        count: usize,
+    },
+
+    #[error("{call_site}: {error}")]
+    Render {
+        call_site: CallSite,
+        error: RenderError,
+    },
 
-    #[error("Invalid img.ini file")]
-    InvalidImgIni,
+    #[error("Novel did not finish after {0} events")]
+    TooManyEvents(usize),
 }
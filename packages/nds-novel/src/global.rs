@@ -0,0 +1,170 @@
+use {
+    crate::{
+        error::GlobalStoreError,
+        interpreter::Variables,
+    },
+    nds_parser::command::VariableStorageType,
+    std::{
+        collections::BTreeMap,
+        fmt::Write as _,
+        fs,
+        io::{
+            self,
+            Write,
+        },
+        path::{
+            Path,
+            PathBuf,
+        },
+    },
+};
+
+/// Name of the global variables file inside the save
+/// directory
+pub const GLOBAL_FILE: &str = "global.vars";
+
+/// First word of the global variables file
+pub const GLOBAL_HEADER: &str = "vrs-global";
+
+/// Version of the global variables format
+pub const GLOBAL_VERSION: u32 = 1;
+
+/// Global variables shared by all saves of the novel,
+/// persisted in the save directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlobalStore {
+    path: PathBuf,
+    variables: BTreeMap<String, u16>,
+}
+
+/// Default save directory of the novel: `save` inside the
+/// novel directory or `<name>-save` next to the novel
+/// archive
+pub fn save_directory(novel: impl AsRef<Path>) -> PathBuf {
+    let novel = novel.as_ref();
+    if novel.is_dir() {
+        return novel.join("save");
+    }
+
+    let mut name = novel.file_stem().unwrap_or_default().to_owned();
+    name.push("-save");
+    novel.with_file_name(name)
+}
+
+impl GlobalStore {
+    /// Open store in the save `directory`, missing file is
+    /// treated as the empty store
+    pub fn open(
+        directory: impl AsRef<Path>,
+    ) -> Result<Self, GlobalStoreError> {
+        let path = directory.as_ref().join(GLOBAL_FILE);
+        let variables = match fs::read_to_string(&path) {
+            Ok(text) => parse(&text)?,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                BTreeMap::new()
+            }
+            Err(e) => return Err(e.into()),
+        };
+
+        Ok(Self { path, variables })
+    }
+
+    /// Copy stored variables into the global storage of
+    /// `variables`
+    pub fn load_into(&self, variables: &mut Variables) {
+        variables
+            .storage_mut(VariableStorageType::Global)
+            .extend(
+                self.variables
+                    .iter()
+                    .map(|(name, value)| (name.clone(), *value)),
+            );
+    }
+
+    /// Take global variables and save them if they were
+    /// changed. Returns whether the store was written
+    pub fn update(
+        &mut self,
+        variables: &Variables,
+    ) -> Result<bool, GlobalStoreError> {
+        let global = variables.storage(VariableStorageType::Global);
+        if *global == self.variables {
+            return Ok(false);
+        }
+
+        self.variables = global.clone();
+        self.save()?;
+
+        Ok(true)
+    }
+
+    /// Write the store. File is replaced atomically, so an
+    /// interrupted write leaves the previous contents
+    pub fn save(&self) -> Result<(), GlobalStoreError> {
+        let mut text = format!("{GLOBAL_HEADER} {GLOBAL_VERSION}\n");
+        for (name, value) in &self.variables {
+            let _ = writeln!(text, "{name} {value}");
+        }
+
+        if let Some(directory) = self.path.parent() {
+            fs::create_dir_all(directory)?;
+        }
+
+        let temporary = self.path.with_extension("vars.tmp");
+        let mut file = fs::File::create(&temporary)?;
+        file.write_all(text.as_bytes())?;
+        file.sync_all()?;
+        fs::rename(&temporary, &self.path)?;
+
+        Ok(())
+    }
+}
+
+impl GlobalStore {
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn variables(&self) -> &BTreeMap<String, u16> {
+        &self.variables
+    }
+
+    pub fn variables_mut(&mut self) -> &mut BTreeMap<String, u16> {
+        &mut self.variables
+    }
+}
+
+fn parse(text: &str) -> Result<BTreeMap<String, u16>, GlobalStoreError> {
+    let mut lines = text
+        .lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim_end_matches('\r')))
+        .filter(|(_, line)| !line.is_empty());
+
+    match lines
+        .next()
+        .and_then(|(_, line)| line.split_once(' '))
+    {
+        Some((GLOBAL_HEADER, version)) => {
+            if version.parse() != Ok(GLOBAL_VERSION) {
+                return Err(GlobalStoreError::UnsupportedVersion(
+                    version.to_owned(),
+                ));
+            }
+        }
+        _ => return Err(GlobalStoreError::NoHeader),
+    }
+
+    lines
+        .map(|(line, text)| {
+            text.split_once(' ')
+                .and_then(|(name, value)| {
+                    Some((name.to_owned(), value.parse().ok()?))
+                })
+                .ok_or_else(|| GlobalStoreError::MalformedLine {
+                    line,
+                    text: text.to_owned(),
+                })
+        })
+        .collect()
+}
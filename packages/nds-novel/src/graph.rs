@@ -0,0 +1,587 @@
+use {
+    crate::{
+        index::{
+            NovelIndex,
+            ScriptIndex,
+        },
+        interpreter::MAIN_SCRIPT,
+    },
+    nds_parser::{
+        command::Command,
+        span::Span,
+    },
+    std::{
+        collections::{
+            BTreeMap,
+            VecDeque,
+        },
+        fmt::Write,
+        ops::Range,
+        path::{
+            Path,
+            PathBuf,
+        },
+    },
+};
+
+/// Why control goes from one block to another
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EdgeKind {
+    /// Block ends since the next one starts
+    Next,
+
+    Goto,
+    Jump,
+
+    /// Outcome of the `if` with the `condition`
+    Branch {
+        condition: String,
+        taken: bool,
+    },
+
+    /// Player picks one of the `options`
+    Choice {
+        options: Vec<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+    Block(usize),
+
+    /// `jump` to the script which does not exist
+    MissingScript(PathBuf),
+
+    /// `goto` or `jump` to the label which is not defined
+    MissingLabel {
+        file: PathBuf,
+        label: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edge {
+    pub kind: EdgeKind,
+    pub target: Target,
+}
+
+/// Commands which are always executed together
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Block {
+    /// Script path relative to the script directory
+    pub file: PathBuf,
+
+    /// Indices of the commands in the script
+    pub commands: Range<usize>,
+
+    /// Location of the first command, `None` for the empty
+    /// script
+    pub span: Option<Span>,
+
+    /// Label the block starts with
+    pub label: Option<String>,
+
+    /// Blocks executed next, empty if the script ends after
+    /// the block
+    pub edges: Vec<Edge>,
+}
+
+/// Control flow graph of all scripts of the novel
+#[derive(Debug, Clone, Default)]
+pub struct ControlFlowGraph {
+    blocks: Vec<Block>,
+    reachable: Vec<bool>,
+}
+
+impl Block {
+    /// Story can not continue after the block: the script
+    /// ends or the block leads to the missing targets only
+    pub fn is_dead_end(&self) -> bool {
+        self.edges
+            .iter()
+            .all(|edge| !matches!(edge.target, Target::Block(..)))
+    }
+}
+
+impl ControlFlowGraph {
+    /// Build graph of the indexed scripts, the story starts
+    /// at the beginning of the [`MAIN_SCRIPT`]
+    pub fn build(index: &NovelIndex) -> Self {
+        let mut graph = Self::default();
+
+        // first block of every script and the block which
+        // contains each command
+        let mut script_blocks = BTreeMap::new();
+        for script in index.scripts() {
+            let first = graph.blocks.len();
+            let leaders = leaders(script);
+            let mut block_of = vec![0; script.commands.len()];
+
+            for (n, &start) in leaders.iter().enumerate() {
+                let end = leaders
+                    .get(n + 1)
+                    .copied()
+                    .unwrap_or(script.commands.len());
+                block_of[start..end].fill(graph.blocks.len());
+
+                graph.blocks.push(Block {
+                    file: script.path.clone(),
+                    commands: start..end,
+                    span: script.commands.get(start).map(|c| c.span),
+                    label: match script
+                        .commands
+                        .get(start)
+                        .map(|c| &c.inner)
+                    {
+                        Some(Command::Label(label)) => Some(label.clone()),
+                        _ => None,
+                    },
+                    edges: Vec::new(),
+                });
+            }
+
+            script_blocks.insert(script.path.as_path(), (first, block_of));
+        }
+
+        for id in 0..graph.blocks.len() {
+            let block = &graph.blocks[id];
+            let script = index
+                .script(&block.file)
+                .expect("blocks are built from the index");
+            let edges = edges(index, script, block, |file, index| {
+                let (first, block_of) = script_blocks.get(file)?;
+                match block_of.get(index) {
+                    Some(&block) => Some(block),
+                    None if block_of.is_empty() => Some(*first),
+                    None => None,
+                }
+            });
+            graph.blocks[id].edges = edges;
+        }
+
+        graph.reachable = vec![false; graph.blocks.len()];
+        let mut queue: VecDeque<usize> = script_blocks
+            .get(Path::new(MAIN_SCRIPT))
+            .map(|(first, _)| *first)
+            .into_iter()
+            .collect();
+        while let Some(id) = queue.pop_front() {
+            if std::mem::replace(&mut graph.reachable[id], true) {
+                continue;
+            }
+
+            queue.extend(graph.blocks[id].edges.iter().filter_map(
+                |edge| match edge.target {
+                    Target::Block(target) => Some(target),
+                    _ => None,
+                },
+            ));
+        }
+
+        graph
+    }
+
+    pub fn blocks(&self) -> &[Block] {
+        &self.blocks
+    }
+
+    /// Whether the block can be reached from the start of
+    /// the story
+    pub fn is_reachable(&self, block: usize) -> bool {
+        self.reachable[block]
+    }
+
+    /// Labels which can not be reached from the start of
+    /// the story
+    pub fn unreachable_labels(
+        &self,
+    ) -> impl Iterator<Item = (&Path, &str)> + '_ {
+        self.blocks
+            .iter()
+            .zip(&self.reachable)
+            .filter(|(_, &reachable)| !reachable)
+            .filter_map(|(block, _)| {
+                Some((block.file.as_path(), block.label.as_deref()?))
+            })
+    }
+
+    /// Reachable blocks after which the story can not
+    /// continue
+    pub fn dead_ends(&self) -> impl Iterator<Item = &Block> + '_ {
+        self.blocks
+            .iter()
+            .zip(&self.reachable)
+            .filter(|(block, &reachable)| reachable && block.is_dead_end())
+            .map(|(block, _)| block)
+    }
+
+    /// Graphviz representation, scripts are drawn as
+    /// clusters. Unreachable blocks are grey, dead ends
+    /// and missing targets are red
+    pub fn to_dot(&self) -> String {
+        let mut dot =
+            String::from("digraph novel {\n    node [shape=box];\n");
+
+        for (n, (file, blocks)) in self.by_file().into_iter().enumerate() {
+            let _ = writeln!(dot, "    subgraph cluster_{n} {{");
+            let _ =
+                writeln!(dot, "        label=\"{}\";", dot_escape(&file));
+            for id in blocks {
+                let block = &self.blocks[id];
+                let _ = write!(
+                    dot,
+                    "        b{id} [label=\"{}\"",
+                    dot_escape(&self.block_title(id)).replace('\n', "\\n")
+                );
+                if !self.reachable[id] {
+                    dot.push_str(
+                        ", style=\"filled,dashed\", fillcolor=lightgrey",
+                    );
+                }
+                if block.is_dead_end() {
+                    dot.push_str(", color=red, penwidth=2");
+                }
+                dot.push_str("];\n");
+            }
+            dot.push_str("    }\n");
+        }
+
+        for (id, edge, target) in self.edges() {
+            let _ = write!(dot, "    b{id} -> {}", target);
+            match edge_label(&edge.kind) {
+                Some(label) => {
+                    let _ =
+                        write!(dot, " [label=\"{}\"]", dot_escape(&label));
+                }
+                None if edge.kind != EdgeKind::Next => {
+                    dot.push_str(" [style=dashed]")
+                }
+                None => {}
+            }
+            dot.push_str(";\n");
+        }
+        for (id, title) in self.missing_targets() {
+            let _ = writeln!(
+                dot,
+                "    {id} [label=\"{}\", shape=octagon, color=red];",
+                dot_escape(&title)
+            );
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+
+    /// Mermaid flowchart, highlighting is the same as in
+    /// [`ControlFlowGraph::to_dot`]
+    pub fn to_mermaid(&self) -> String {
+        let mut mermaid = String::from("flowchart TD\n");
+
+        for (n, (file, blocks)) in self.by_file().into_iter().enumerate() {
+            let _ = writeln!(
+                mermaid,
+                "    subgraph s{n} [\"{}\"]",
+                mermaid_escape(&file)
+            );
+            for id in blocks {
+                let _ = writeln!(
+                    mermaid,
+                    "        b{id}[\"{}\"]",
+                    mermaid_escape(&self.block_title(id))
+                        .replace('\n', "<br/>")
+                );
+            }
+            mermaid.push_str("    end\n");
+        }
+
+        for (id, title) in self.missing_targets() {
+            let _ = writeln!(
+                mermaid,
+                "    {id}{{{{\"{}\"}}}}",
+                mermaid_escape(&title)
+            );
+        }
+        for (id, edge, target) in self.edges() {
+            let arrow = match edge.kind {
+                EdgeKind::Next => "-->",
+                _ => "-.->",
+            };
+            let _ = match edge_label(&edge.kind) {
+                Some(label) => writeln!(
+                    mermaid,
+                    "    b{id} {arrow}|\"{}\"| {}",
+                    mermaid_escape(&label),
+                    target
+                ),
+                None => writeln!(mermaid, "    b{id} {arrow} {}", target),
+            };
+        }
+
+        mermaid.push_str(
+            "    classDef unreachable fill:#ddd,stroke-dasharray:5 5\n    \
+             classDef deadend stroke:#d00,stroke-width:2px\n",
+        );
+        for (class, ids) in [
+            (
+                "unreachable",
+                (0..self.blocks.len())
+                    .filter(|&id| !self.reachable[id])
+                    .map(|id| format!("b{id}"))
+                    .collect::<Vec<_>>(),
+            ),
+            (
+                "deadend",
+                (0..self.blocks.len())
+                    .filter(|&id| self.blocks[id].is_dead_end())
+                    .map(|id| format!("b{id}"))
+                    .chain(self.missing_targets().map(|(id, _)| id))
+                    .collect(),
+            ),
+        ] {
+            if !ids.is_empty() {
+                let _ = writeln!(
+                    mermaid,
+                    "    class {} {class}",
+                    ids.join(",")
+                );
+            }
+        }
+
+        mermaid
+    }
+
+    /// Block ids grouped by the script path
+    fn by_file(&self) -> BTreeMap<String, Vec<usize>> {
+        let mut files = BTreeMap::<_, Vec<_>>::new();
+        for (id, block) in self.blocks.iter().enumerate() {
+            files
+                .entry(block.file.display().to_string())
+                .or_default()
+                .push(id);
+        }
+
+        files
+    }
+
+    fn block_title(&self, id: usize) -> String {
+        let block = &self.blocks[id];
+        let mut title = match block.span {
+            Some(span) => {
+                format!("{}:{}", block.file.display(), span.line)
+            }
+            None => block.file.display().to_string(),
+        };
+        if let Some(label) = &block.label {
+            let _ = write!(title, "\nlabel {label}");
+        }
+
+        title
+    }
+
+    /// Edges with the node id of the target
+    fn edges(&self) -> impl Iterator<Item = (usize, &Edge, String)> + '_ {
+        let mut missing = 0;
+        self.blocks
+            .iter()
+            .enumerate()
+            .flat_map(|(id, block)| {
+                block.edges.iter().map(move |e| (id, e))
+            })
+            .map(move |(id, edge)| {
+                let target = match edge.target {
+                    Target::Block(target) => format!("b{target}"),
+                    _ => {
+                        missing += 1;
+                        format!("m{}", missing - 1)
+                    }
+                };
+
+                (id, edge, target)
+            })
+    }
+
+    /// Nodes for the missing targets, in the order of
+    /// [`ControlFlowGraph::edges`]
+    fn missing_targets(
+        &self,
+    ) -> impl Iterator<Item = (String, String)> + '_ {
+        self.blocks
+            .iter()
+            .flat_map(|block| &block.edges)
+            .filter_map(|edge| match &edge.target {
+                Target::Block(..) => None,
+                Target::MissingScript(file) => {
+                    Some(format!("missing script {}", file.display()))
+                }
+                Target::MissingLabel { file, label } => Some(format!(
+                    "missing label {label} in {}",
+                    file.display()
+                )),
+            })
+            .enumerate()
+            .map(|(n, title)| (format!("m{n}"), title))
+    }
+}
+
+/// Indices of the commands which start the blocks
+fn leaders(script: &ScriptIndex) -> Vec<usize> {
+    let commands = &script.commands;
+    let mut leaders = vec![0];
+
+    for (index, command) in commands.iter().enumerate() {
+        match &command.inner {
+            Command::Label(..) | Command::EndIf => leaders.push(index),
+            Command::Goto(..)
+            | Command::Jump { .. }
+            | Command::Choice { .. } => leaders.push(index + 1),
+            Command::If { .. } => {
+                leaders.push(index + 1);
+                leaders.push(matching_end_if(script, index));
+            }
+
+            _ => {}
+        }
+    }
+
+    leaders.retain(|&leader| leader < commands.len() || leader == 0);
+    leaders.sort_unstable();
+    leaders.dedup();
+    leaders
+}
+
+/// Index of the `fi` closing the `if` at `index`, or the
+/// script length if it is not closed
+fn matching_end_if(script: &ScriptIndex, index: usize) -> usize {
+    let mut depth = 0_usize;
+    for (n, command) in script.commands.iter().enumerate().skip(index + 1)
+    {
+        match command.inner {
+            Command::If { .. } => depth += 1,
+            Command::EndIf if depth == 0 => return n,
+            Command::EndIf => depth -= 1,
+
+            _ => {}
+        }
+    }
+
+    script.commands.len()
+}
+
+fn edges(
+    index: &NovelIndex,
+    script: &ScriptIndex,
+    block: &Block,
+    block_at: impl Fn(&Path, usize) -> Option<usize>,
+) -> Vec<Edge> {
+    let edge = |kind, file: &Path, at| {
+        block_at(file, at).map(|target| Edge {
+            kind,
+            target: Target::Block(target),
+        })
+    };
+    let to_label = |kind, file: &Path, label: &str| {
+        let target = index
+            .script(file)
+            .and_then(|script| script.labels.get(label))
+            .and_then(|&at| block_at(file, at));
+
+        Edge {
+            kind,
+            target: match target {
+                Some(target) => Target::Block(target),
+                None => Target::MissingLabel {
+                    file: file.to_owned(),
+                    label: label.to_owned(),
+                },
+            },
+        }
+    };
+
+    let file = script.path.as_path();
+    if block.commands.is_empty() {
+        return Vec::new();
+    }
+    let last = block.commands.end - 1;
+
+    match &script.commands[last].inner {
+        Command::Goto(label) => {
+            vec![to_label(EdgeKind::Goto, file, label)]
+        }
+        Command::Jump { file, label } => {
+            if index.script(file).is_none() {
+                return vec![Edge {
+                    kind: EdgeKind::Jump,
+                    target: Target::MissingScript(file.clone()),
+                }];
+            }
+
+            match label {
+                Some(label) => vec![to_label(EdgeKind::Jump, file, label)],
+                None => edge(EdgeKind::Jump, file, 0)
+                    .into_iter()
+                    .collect(),
+            }
+        }
+        Command::If {
+            name,
+            operator,
+            rhs,
+        } => {
+            let condition = format!("{name} {operator} {rhs}");
+            [
+                edge(
+                    EdgeKind::Branch {
+                        condition: condition.clone(),
+                        taken: true,
+                    },
+                    file,
+                    last + 1,
+                ),
+                edge(
+                    EdgeKind::Branch {
+                        condition,
+                        taken: false,
+                    },
+                    file,
+                    matching_end_if(script, last),
+                ),
+            ]
+            .into_iter()
+            .flatten()
+            .collect()
+        }
+        Command::Choice { options } => edge(
+            EdgeKind::Choice {
+                options: options.iter().map(ToString::to_string).collect(),
+            },
+            file,
+            last + 1,
+        )
+        .into_iter()
+        .collect(),
+
+        _ => edge(EdgeKind::Next, file, last + 1)
+            .into_iter()
+            .collect(),
+    }
+}
+
+fn edge_label(kind: &EdgeKind) -> Option<String> {
+    match kind {
+        EdgeKind::Next | EdgeKind::Goto | EdgeKind::Jump => None,
+        EdgeKind::Branch {
+            condition,
+            taken: true,
+        } => Some(condition.clone()),
+        EdgeKind::Branch { taken: false, .. } => Some("else".to_owned()),
+        EdgeKind::Choice { options } => Some(options.join(" | ")),
+    }
+}
+
+fn dot_escape(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn mermaid_escape(text: &str) -> String {
+    text.replace('"', "#quot;")
+}
@@ -0,0 +1,250 @@
+use {
+    crate::{
+        error::ImgIniError,
+        ini::{
+            self,
+            Line,
+        },
+    },
+    std::{
+        collections::BTreeMap,
+        fmt,
+        str::FromStr,
+    },
+};
+
+/// Keys of the [`TextBox`] in the order they are written
+const TEXT_BOX_KEYS: [&str; 4] =
+    ["textbox_x", "textbox_y", "textbox_width", "textbox_height"];
+
+/// Contents of the `img.ini`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImgIni {
+    /// Width of the images the novel was made for
+    pub width: u16,
+
+    /// Height of the images the novel was made for
+    pub height: u16,
+
+    /// `fontsize`
+    pub font_size: Option<u16>,
+
+    /// `textbox_x`, `textbox_y`, `textbox_width` and
+    /// `textbox_height`, which are given all or none
+    pub text_box: Option<TextBox>,
+
+    /// `textcolor`
+    pub text_color: Option<Rgb>,
+
+    /// Keys unknown to the parser in order of appearance,
+    /// written back as is. Engines add their own settings
+    /// whose meaning differs between them, so these stay
+    /// untyped
+    pub extra: Vec<(String, String)>,
+}
+
+/// Text box rectangle in the screen pixels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TextBox {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+/// Color written as `RRGGBB` hex digits
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl ImgIni {
+    pub const fn new(width: u16, height: u16) -> Self {
+        Self {
+            width,
+            height,
+            font_size: None,
+            text_box: None,
+            text_color: None,
+            extra: Vec::new(),
+        }
+    }
+
+    /// Parse `img.ini` bytes in any of the supported
+    /// encodings
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, ImgIniError> {
+        ini::decode(bytes).parse()
+    }
+
+    pub const fn resolution(&self) -> (u16, u16) {
+        (self.width, self.height)
+    }
+
+    /// Get value of the unknown key
+    pub fn get<T: FromStr>(
+        &self,
+        key: &str,
+    ) -> Option<Result<T, ImgIniError>> {
+        self.extra
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, value)| parse_value(key, value))
+    }
+
+    /// Set value of the unknown key, keeping its position
+    /// if the key is already present
+    pub fn set(&mut self, key: &str, value: impl ToString) {
+        let value = value.to_string();
+        match self.extra.iter_mut().find(|(k, _)| k == key) {
+            Some((_, v)) => *v = value,
+            None => self.extra.push((key.to_owned(), value)),
+        }
+    }
+}
+
+impl Rgb {
+    /// Parse `RRGGBB` hex digits
+    pub fn from_hex(s: &str) -> Option<Self> {
+        if s.len() != 6 || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return None;
+        }
+
+        let channel =
+            |n: usize| u8::from_str_radix(&s[n * 2..n * 2 + 2], 16);
+        Some(Self {
+            r: channel(0).ok()?,
+            g: channel(1).ok()?,
+            b: channel(2).ok()?,
+        })
+    }
+}
+
+impl FromStr for ImgIni {
+    type Err = ImgIniError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut known = BTreeMap::new();
+        let mut extra = Vec::new();
+
+        for line in ini::lines(s) {
+            let (line, key, value) = match line {
+                Line::Entry { number, key, value } => (number, key, value),
+                Line::Malformed { number, text } => {
+                    return Err(ImgIniError::MalformedLine {
+                        line: number,
+                        text: text.to_owned(),
+                    })
+                }
+            };
+
+            match key {
+                "width" | "height" | "fontsize" | "textcolor" => {}
+                key if TEXT_BOX_KEYS.contains(&key) => {}
+
+                key => {
+                    extra.push((key.to_owned(), value.to_owned()));
+                    continue;
+                }
+            }
+
+            if known.insert(key, value).is_some() {
+                return Err(ImgIniError::DuplicateKey {
+                    line,
+                    key: key.to_owned(),
+                });
+            }
+        }
+
+        let number = |key: &'static str| {
+            known
+                .get(key)
+                .map(|value| parse_value::<u16>(key, value))
+                .transpose()
+        };
+        let required =
+            |key| number(key)?.ok_or(ImgIniError::MissingKey(key));
+
+        let text_box = if TEXT_BOX_KEYS
+            .iter()
+            .any(|key| known.contains_key(key))
+        {
+            let [x, y, width, height] = TEXT_BOX_KEYS;
+            Some(TextBox {
+                x: required(x)?,
+                y: required(y)?,
+                width: required(width)?,
+                height: required(height)?,
+            })
+        } else {
+            None
+        };
+
+        Ok(Self {
+            width: required("width")?,
+            height: required("height")?,
+            font_size: number("fontsize")?,
+            text_box,
+            text_color: known
+                .get("textcolor")
+                .map(|value| {
+                    Rgb::from_hex(value).ok_or_else(|| {
+                        ImgIniError::InvalidValue {
+                            key: "textcolor".to_owned(),
+                            value: (*value).to_owned(),
+                        }
+                    })
+                })
+                .transpose()?,
+            extra,
+        })
+    }
+}
+
+/// Parse the value of the `key`
+fn parse_value<T: FromStr>(
+    key: &str,
+    value: &str,
+) -> Result<T, ImgIniError> {
+    value
+        .parse()
+        .map_err(|_| ImgIniError::InvalidValue {
+            key: key.to_owned(),
+            value: value.to_owned(),
+        })
+}
+
+/// Writes `img.ini` contents
+impl fmt::Display for ImgIni {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "width={}", self.width)?;
+        writeln!(f, "height={}", self.height)?;
+        if let Some(font_size) = self.font_size {
+            writeln!(f, "fontsize={font_size}")?;
+        }
+        if let Some(text_box) = self.text_box {
+            let values =
+                [text_box.x, text_box.y, text_box.width, text_box.height];
+            for (key, value) in TEXT_BOX_KEYS.iter().zip(values) {
+                writeln!(f, "{key}={value}")?;
+            }
+        }
+        if let Some(text_color) = self.text_color {
+            writeln!(f, "textcolor={text_color}")?;
+        }
+
+        for (key, value) in &self.extra {
+            writeln!(f, "{key}={value}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Formats as `RRGGBB`
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:02x}{:02x}{:02x}", self.r, self.g, self.b)
+    }
+}
@@ -0,0 +1,258 @@
+use {
+    crate::{
+        resource::ResourceSource,
+        variables,
+    },
+    nds_parser::{
+        command::{
+            Command,
+            MusicFile,
+            SoundLooping,
+        },
+        error::Diagnostic,
+        parser::{
+            InvalidCommands,
+            ParseScript,
+        },
+        span::{
+            Span,
+            Spanned,
+        },
+    },
+    std::{
+        collections::{
+            BTreeMap,
+            BTreeSet,
+        },
+        io,
+        path::{
+            Path,
+            PathBuf,
+        },
+        sync::Arc,
+    },
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ResourceKind {
+    Background,
+    Foreground,
+    Sound,
+    Music,
+}
+
+/// Resource used by the command at `index`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResourceReference {
+    pub kind: ResourceKind,
+    pub path: PathBuf,
+
+    pub index: usize,
+    pub span: Span,
+}
+
+/// `jump` command at `index`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct JumpReference {
+    pub file: PathBuf,
+    pub label: Option<String>,
+
+    pub index: usize,
+    pub span: Span,
+}
+
+/// Everything known about the single script
+#[derive(Debug, Clone)]
+pub struct ScriptIndex {
+    /// Path relative to the script directory
+    pub path: PathBuf,
+
+    /// Commands parsed in the lenient mode, so indices
+    /// match the ones of the [`crate::script::Script`]
+    pub commands: Vec<Spanned<Command>>,
+    pub diagnostics: Vec<Diagnostic>,
+
+    /// Label name to the command index
+    pub labels: BTreeMap<String, usize>,
+    pub jumps: Vec<JumpReference>,
+
+    /// Variables read or written by the commands, see
+    /// [`crate::variables::VariableAnalysis`]
+    pub variables: BTreeSet<String>,
+    pub resources: Vec<ResourceReference>,
+
+    /// Why the script could not be read, e.g. it is not
+    /// UTF-8. Such script has no commands
+    pub read_error: Option<Arc<io::Error>>,
+}
+
+/// Index of all scripts inside the novel
+#[derive(Debug, Clone, Default)]
+pub struct NovelIndex {
+    scripts: BTreeMap<PathBuf, ScriptIndex>,
+}
+
+impl NovelIndex {
+    /// Index every `*.scr` file of the script directory.
+    /// Scripts which fail to read are indexed with the
+    /// [`ScriptIndex::read_error`]
+    pub fn build(scripts: &dyn ResourceSource) -> io::Result<Self> {
+        let mut index = Self::default();
+        for path in scripts.files()? {
+            if path.extension().is_some_and(|e| e == "scr") {
+                index.insert(match scripts.read_to_string(&path) {
+                    Ok(source) => ScriptIndex::new(path, &source),
+                    Err(error) => ScriptIndex::unreadable(path, error),
+                });
+            }
+        }
+
+        Ok(index)
+    }
+
+    pub fn insert(&mut self, script: ScriptIndex) {
+        self.scripts.insert(script.path.clone(), script);
+    }
+
+    pub fn script(&self, path: impl AsRef<Path>) -> Option<&ScriptIndex> {
+        self.scripts.get(path.as_ref())
+    }
+
+    pub fn scripts(&self) -> impl Iterator<Item = &ScriptIndex> {
+        self.scripts.values()
+    }
+
+    /// Scripts which define the `label`
+    pub fn label_definitions<'a>(
+        &'a self,
+        label: &'a str,
+    ) -> impl Iterator<Item = (&'a ScriptIndex, usize)> + 'a {
+        self.scripts().filter_map(move |script| {
+            script
+                .labels
+                .get(label)
+                .map(|&index| (script, index))
+        })
+    }
+
+    /// Jumps targeting the `file`
+    pub fn jumps_to<'a>(
+        &'a self,
+        file: &'a Path,
+    ) -> impl Iterator<Item = (&'a ScriptIndex, &'a JumpReference)> + 'a
+    {
+        self.scripts().flat_map(move |script| {
+            script
+                .jumps
+                .iter()
+                .filter(move |jump| jump.file == file)
+                .map(move |jump| (script, jump))
+        })
+    }
+
+    /// Scripts referencing the variable
+    pub fn variable_users<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a ScriptIndex> + 'a {
+        self.scripts()
+            .filter(move |script| script.variables.contains(name))
+    }
+
+    /// Commands using the resource
+    pub fn resource_users<'a>(
+        &'a self,
+        kind: ResourceKind,
+        path: &'a Path,
+    ) -> impl Iterator<Item = (&'a ScriptIndex, &'a ResourceReference)> + 'a
+    {
+        self.scripts().flat_map(move |script| {
+            script
+                .resources
+                .iter()
+                .filter(move |r| r.kind == kind && r.path == path)
+                .map(move |r| (script, r))
+        })
+    }
+}
+
+impl ScriptIndex {
+    pub fn new(path: impl Into<PathBuf>, source: &str) -> Self {
+        let outcome =
+            source.parse_script_lenient(InvalidCommands::Placeholder);
+        let mut index = Self {
+            path: path.into(),
+            commands: Vec::new(),
+            diagnostics: outcome.diagnostics,
+
+            labels: BTreeMap::new(),
+            jumps: Vec::new(),
+            variables: BTreeSet::new(),
+            resources: Vec::new(),
+            read_error: None,
+        };
+
+        for (position, command) in outcome.commands.iter().enumerate() {
+            index.visit(position, command);
+        }
+        index.commands = outcome.commands;
+
+        index
+    }
+
+    /// Script which failed to read with the `error`
+    pub fn unreadable(path: impl Into<PathBuf>, error: io::Error) -> Self {
+        Self {
+            read_error: Some(Arc::new(error)),
+            ..Self::new(path, "")
+        }
+    }
+
+    fn visit(&mut self, index: usize, command: &Spanned<Command>) {
+        self.variables.extend(
+            variables::accesses(&command.inner)
+                .into_iter()
+                .map(|(name, ..)| name.to_owned()),
+        );
+
+        let span = command.span;
+        let mut resource = |kind, path: &PathBuf| {
+            self.resources.push(ResourceReference {
+                kind,
+                path: path.clone(),
+                index,
+                span,
+            })
+        };
+
+        match &command.inner {
+            Command::BgLoad { file, .. } => {
+                resource(ResourceKind::Background, file)
+            }
+            Command::SetImg { file, .. } => {
+                resource(ResourceKind::Foreground, file)
+            }
+            Command::Sound(
+                SoundLooping::Infinite { file }
+                | SoundLooping::Count { file, .. },
+            ) => resource(ResourceKind::Sound, file),
+            Command::Music {
+                file: MusicFile::Path(file),
+            } => resource(ResourceKind::Music, file),
+
+            Command::Label(label) => {
+                self.labels.insert(label.clone(), index);
+            }
+            Command::Jump { file, label } => {
+                self.jumps.push(JumpReference {
+                    file: file.clone(),
+                    label: label.clone(),
+                    index,
+                    span,
+                })
+            }
+
+            _ => {}
+        }
+    }
+}
@@ -1 +1,106 @@
+use {
+    crate::{
+        error::InfoParseError,
+        ini::{
+            self,
+            Line,
+        },
+    },
+    std::str::FromStr,
+};
 
+/// Contents of the `info.txt`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NovelInfo {
+    pub title: String,
+    pub author: Option<String>,
+    pub version: Option<String>,
+    pub description: Option<String>,
+
+    /// Keys unknown to the parser in order of appearance
+    pub extra: Vec<(String, String)>,
+
+    /// Lines which were skipped or overridden by a later
+    /// line
+    pub warnings: Vec<InfoParseError>,
+}
+
+impl NovelInfo {
+    /// Parse `info.txt` bytes in any of the supported
+    /// encodings
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InfoParseError> {
+        ini::decode(bytes).parse()
+    }
+
+    /// Get value of the unknown key
+    pub fn extra(&self, key: &str) -> Option<&str> {
+        self.extra
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v.as_str())
+    }
+}
+
+impl FromStr for NovelInfo {
+    type Err = InfoParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut title = None;
+        let mut info = Self {
+            title: String::new(),
+            author: None,
+            version: None,
+            description: None,
+            extra: Vec::new(),
+            warnings: Vec::new(),
+        };
+
+        for line in ini::lines(s) {
+            let (line, key, value) = match line {
+                Line::Entry { number, key, value } => (number, key, value),
+                Line::Malformed { number, text } => {
+                    info.warnings.push(InfoParseError::MalformedLine {
+                        line: number,
+                        text: text.to_owned(),
+                    });
+                    continue;
+                }
+            };
+            let duplicate = InfoParseError::DuplicateKey {
+                line,
+                key: key.to_owned(),
+            };
+
+            let slot = match key {
+                "title" => &mut title,
+                "author" => &mut info.author,
+                "version" => &mut info.version,
+                "description" => &mut info.description,
+
+                key => {
+                    match info.extra.iter_mut().find(|(k, _)| k == key) {
+                        Some((_, slot)) => {
+                            info.warnings.push(duplicate);
+                            value.clone_into(slot);
+                        }
+                        None => info
+                            .extra
+                            .push((key.to_owned(), value.to_owned())),
+                    }
+                    continue;
+                }
+            };
+
+            if slot.is_some() {
+                info.warnings.push(duplicate);
+            }
+            *slot = Some(value.to_owned());
+        }
+
+        info.title = title
+            .filter(|title| !title.is_empty())
+            .ok_or(InfoParseError::NoTitle)?;
+
+        Ok(info)
+    }
+}
@@ -0,0 +1,57 @@
+/// Decode configuration file contents. UTF-8 and UTF-16
+/// with the byte order mark are supported, text which is
+/// not a valid UTF-8 is decoded as Latin-1
+pub(crate) fn decode(bytes: &[u8]) -> String {
+    fn utf16(bytes: &[u8], from: fn([u8; 2]) -> u16) -> String {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| from([pair[0], pair[1]]))
+            .collect();
+
+        String::from_utf16_lossy(&units)
+    }
+
+    match bytes {
+        [0xEF, 0xBB, 0xBF, rest @ ..] => {
+            String::from_utf8_lossy(rest).into_owned()
+        }
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+
+        bytes => match std::str::from_utf8(bytes) {
+            Ok(text) => text.to_owned(),
+            Err(_) => bytes.iter().map(|&b| char::from(b)).collect(),
+        },
+    }
+}
+
+/// Meaningful line of the configuration file
+pub(crate) enum Line<'a> {
+    Entry {
+        number: usize,
+        key: &'a str,
+        value: &'a str,
+    },
+
+    /// Line which is not a `key=value` pair
+    Malformed { number: usize, text: &'a str },
+}
+
+/// Iterate over non-empty lines, lines starting with `#` or
+/// `;` are comments
+pub(crate) fn lines(text: &str) -> impl Iterator<Item = Line<'_>> {
+    text.lines()
+        .enumerate()
+        .map(|(index, line)| (index + 1, line.trim()))
+        .filter(|(_, line)| {
+            !line.is_empty() && !line.starts_with(['#', ';'])
+        })
+        .map(|(number, line)| match line.split_once('=') {
+            Some((key, value)) if !key.trim().is_empty() => Line::Entry {
+                number,
+                key: key.trim(),
+                value: value.trim(),
+            },
+            _ => Line::Malformed { number, text: line },
+        })
+}
@@ -0,0 +1,520 @@
+use {
+    crate::{
+        error::InterpreterError,
+        random::{
+            RandomSource,
+            XorShift,
+        },
+        script::{
+            Script,
+            ScriptControlFlow,
+        },
+    },
+    nds_parser::{
+        command::{
+            ChoiceOption,
+            ClearTextType,
+            Command,
+            IfRhs,
+            MusicFile,
+            SoundLooping,
+            VariableModifier,
+            VariableStorageType,
+        },
+        text::{
+            MissingVariable,
+            Text,
+            VariableLookup,
+        },
+    },
+    std::{
+        collections::BTreeMap,
+        path::{
+            Path,
+            PathBuf,
+        },
+    },
+};
+
+/// Name of the variable which receives the selected choice
+/// option (1-based, as in the original engine)
+pub const SELECTED_VARIABLE: &str = "selected";
+
+/// Script the novel starts from
+pub const MAIN_SCRIPT: &str = "main.scr";
+
+/// Events which should be presented by the frontend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// Show text in the text box
+    Text(Text),
+
+    /// Clear the text box
+    ClearText(ClearTextType),
+
+    /// Load background image
+    Background {
+        file: PathBuf,
+        fadetime: u16,
+    },
+
+    /// Draw foreground image at the specified coordinates
+    Foreground {
+        file: PathBuf,
+        coordinates: (u16, u16),
+    },
+
+    Sound(SoundLooping),
+    Music(MusicFile),
+
+    /// Wait for the player to pick one of the options,
+    /// selected option must be passed to
+    /// [`Interpreter::choose`]
+    Choice {
+        options: Vec<String>,
+    },
+
+    Delay {
+        frames: u16,
+    },
+
+    /// Execution continues in another script, it must be
+    /// loaded by the frontend and passed to
+    /// [`Interpreter::load_script`]
+    Jump {
+        file: PathBuf,
+        label: Option<String>,
+    },
+
+    /// Script execution is finished
+    Finished,
+}
+
+/// Local and global variable stores
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub struct Variables {
+    local: BTreeMap<String, u16>,
+    global: BTreeMap<String, u16>,
+}
+
+/// What is currently presented to the player
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Scene {
+    pub background: Option<PathBuf>,
+
+    /// Foreground images in the drawing order, cleared by
+    /// the next background
+    pub foreground: Vec<(PathBuf, (u16, u16))>,
+    pub music: Option<PathBuf>,
+
+    /// Texts shown since the last history clear
+    pub history: Vec<Text>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Interpreter {
+    script: Script,
+
+    /// Path of the current script relative to the script
+    /// directory
+    file: PathBuf,
+    variables: Variables,
+    scene: Scene,
+
+    /// Number of options of the choice we are waiting for
+    pending_choice: Option<usize>,
+    random: Box<dyn RandomSource>,
+
+    /// How the choice options which are not set variables
+    /// are shown
+    missing_variables: MissingVariable,
+}
+
+impl Variables {
+    /// Get variable value, local variables shadow global
+    /// ones. Unset variables are treated as `0`
+    pub fn get(&self, name: &str) -> u16 {
+        self.local
+            .get(name)
+            .or_else(|| self.global.get(name))
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn set(
+        &mut self,
+        name: impl Into<String>,
+        value: u16,
+        storage: VariableStorageType,
+    ) {
+        self.storage_mut(storage)
+            .insert(name.into(), value);
+    }
+
+    pub fn storage(
+        &self,
+        storage: VariableStorageType,
+    ) -> &BTreeMap<String, u16> {
+        match storage {
+            VariableStorageType::Local => &self.local,
+            VariableStorageType::Global => &self.global,
+        }
+    }
+
+    pub fn storage_mut(
+        &mut self,
+        storage: VariableStorageType,
+    ) -> &mut BTreeMap<String, u16> {
+        match storage {
+            VariableStorageType::Local => &mut self.local,
+            VariableStorageType::Global => &mut self.global,
+        }
+    }
+}
+
+/// Unset variables are missing, unlike in
+/// [`Variables::get`]
+impl VariableLookup for Variables {
+    fn lookup(&self, name: &str) -> Option<u16> {
+        self.local
+            .get(name)
+            .or_else(|| self.global.get(name))
+            .copied()
+    }
+}
+
+impl Scene {
+    /// Update the scene with the presentation event
+    pub fn apply(&mut self, event: &Event) {
+        match event {
+            Event::Background { file, .. } => {
+                self.background = Some(file.clone());
+                self.foreground.clear();
+            }
+            Event::Foreground { file, coordinates } => {
+                self.foreground.push((file.clone(), *coordinates))
+            }
+            Event::Music(MusicFile::Path(file)) => {
+                self.music = Some(file.clone())
+            }
+            Event::Music(MusicFile::StopPlaying) => self.music = None,
+            Event::Text(text) => self.history.push(text.clone()),
+            Event::ClearText(ClearTextType::TextBufferInclHistory) => {
+                self.history.clear()
+            }
+
+            _ => {}
+        }
+    }
+}
+
+impl Interpreter {
+    /// Run commands until the next presentation event
+    pub fn step(&mut self) -> Result<Event, InterpreterError> {
+        let event = self.next_event()?;
+        self.scene.apply(&event);
+
+        Ok(event)
+    }
+
+    fn next_event(&mut self) -> Result<Event, InterpreterError> {
+        if self.pending_choice.is_some() {
+            return Err(InterpreterError::ChoicePending);
+        }
+
+        loop {
+            let command = match self.script.next_command() {
+                ScriptControlFlow::Execute(command) => command,
+                ScriptControlFlow::Stopped => return Ok(Event::Finished),
+            };
+
+            return Ok(match command {
+                Command::BgLoad { file, fadetime } => Event::Background {
+                    file: file.clone(),
+                    fadetime: *fadetime,
+                },
+                Command::SetImg { file, coordinates } => {
+                    Event::Foreground {
+                        file: file.clone(),
+                        coordinates: *coordinates,
+                    }
+                }
+                Command::Sound(looping) => Event::Sound(looping.clone()),
+                Command::Music { file } => Event::Music(file.clone()),
+                Command::Text(text) => Event::Text(text.clone()),
+                Command::ClearText(ty) => Event::ClearText(*ty),
+                Command::Delay { frames } => {
+                    Event::Delay { frames: *frames }
+                }
+                Command::Jump { file, label } => Event::Jump {
+                    file: file.clone(),
+                    label: label.clone(),
+                },
+
+                Command::Choice { options } => {
+                    let options = options
+                        .iter()
+                        .map(|option| match option {
+                            ChoiceOption::Option(o) => Ok(o.clone()),
+                            ChoiceOption::Variable(name) => {
+                                self.variables.render_variable(
+                                    name,
+                                    self.missing_variables,
+                                )
+                            }
+                        })
+                        .collect::<Result<Vec<_>, _>>()?;
+                    self.pending_choice = Some(options.len());
+
+                    Event::Choice { options }
+                }
+
+                Command::SetVar {
+                    name,
+                    accumulator,
+                    modifier,
+                    storage,
+                } => {
+                    let current =
+                        self.variables.storage(*storage).get(name);
+                    let value = match modifier {
+                        VariableModifier::Assign => *accumulator,
+                        VariableModifier::Add => current
+                            .copied()
+                            .unwrap_or(0)
+                            .saturating_add(*accumulator),
+                        VariableModifier::Sub => current
+                            .copied()
+                            .unwrap_or(0)
+                            .saturating_sub(*accumulator),
+
+                        m => {
+                            return Err(
+                                InterpreterError::UnsupportedModifier(*m),
+                            )
+                        }
+                    };
+
+                    self.variables.set(name.clone(), value, *storage);
+                    continue;
+                }
+
+                Command::Random { variable, range } => {
+                    let value = self.random.next_in(range.clone());
+                    self.variables.set(
+                        variable.clone(),
+                        value,
+                        VariableStorageType::Local,
+                    );
+                    continue;
+                }
+
+                Command::If {
+                    name,
+                    operator,
+                    rhs,
+                } => {
+                    let rhs = match rhs {
+                        IfRhs::Number(n) => *n,
+                        IfRhs::Variable(v) => self.variables.get(v),
+                    };
+
+                    if !operator.evaluate(self.variables.get(name), rhs) {
+                        Self::skip_branch(&mut self.script);
+                    }
+                    continue;
+                }
+
+                Command::Goto(label) => {
+                    let label = label.clone();
+                    self.script.jump_to_label(&label).map_err(|_| {
+                        InterpreterError::LabelNotFound { label }
+                    })?;
+                    continue;
+                }
+
+                Command::Label(..) | Command::EndIf => continue,
+                Command::Invalid(text) => {
+                    return Err(InterpreterError::InvalidCommand(
+                        text.clone(),
+                    ))
+                }
+            });
+        }
+    }
+
+    /// Pass the option selected by the player, `option` is
+    /// 0-based index into [`Event::Choice`] options
+    pub fn choose(
+        &mut self,
+        option: usize,
+    ) -> Result<(), InterpreterError> {
+        let count = self
+            .pending_choice
+            .ok_or(InterpreterError::NoChoicePending)?;
+        if option >= count {
+            return Err(InterpreterError::ChoiceOutOfRange {
+                option,
+                count,
+            });
+        }
+
+        self.pending_choice = None;
+        self.variables.set(
+            SELECTED_VARIABLE,
+            option as u16 + 1,
+            VariableStorageType::Local,
+        );
+
+        Ok(())
+    }
+
+    /// Continue execution in the `script` located at
+    /// `file`, optionally starting from the `label`
+    pub fn load_script(
+        &mut self,
+        file: impl Into<PathBuf>,
+        mut script: Script,
+        label: Option<&str>,
+    ) -> Result<(), InterpreterError> {
+        if let Some(label) = label {
+            script.jump_to_label(label).map_err(|_| {
+                InterpreterError::LabelNotFound {
+                    label: label.to_owned(),
+                }
+            })?;
+        }
+
+        self.script = script;
+        self.file = file.into();
+        Ok(())
+    }
+
+    /// Skip commands until the matching `fi`
+    fn skip_branch(script: &mut Script) {
+        let mut depth = 1_usize;
+
+        while let ScriptControlFlow::Execute(command) =
+            script.next_command()
+        {
+            match command {
+                Command::If { .. } => depth += 1,
+                Command::EndIf => {
+                    depth -= 1;
+                    if depth == 0 {
+                        break;
+                    }
+                }
+
+                _ => {}
+            }
+        }
+    }
+}
+
+impl Interpreter {
+    pub fn script(&self) -> &Script {
+        &self.script
+    }
+
+    pub fn script_mut(&mut self) -> &mut Script {
+        &mut self.script
+    }
+
+    /// Path of the current script relative to the script
+    /// directory
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    pub fn scene(&self) -> &Scene {
+        &self.scene
+    }
+
+    pub fn scene_mut(&mut self) -> &mut Scene {
+        &mut self.scene
+    }
+
+    pub fn variables(&self) -> &Variables {
+        &self.variables
+    }
+
+    pub fn variables_mut(&mut self) -> &mut Variables {
+        &mut self.variables
+    }
+
+    pub fn random(&self) -> &dyn RandomSource {
+        self.random.as_ref()
+    }
+
+    pub fn random_mut(&mut self) -> &mut dyn RandomSource {
+        self.random.as_mut()
+    }
+
+    pub fn set_random(&mut self, random: impl RandomSource + 'static) {
+        self.random = Box::new(random);
+    }
+
+    pub fn missing_variables(&self) -> MissingVariable {
+        self.missing_variables
+    }
+
+    pub fn set_missing_variables(&mut self, missing: MissingVariable) {
+        self.missing_variables = missing;
+    }
+
+    /// Whether the interpreter waits for
+    /// [`Interpreter::choose`]
+    pub const fn is_waiting_for_choice(&self) -> bool {
+        self.pending_choice.is_some()
+    }
+}
+
+impl Interpreter {
+    /// Create interpreter of the script located at `file`
+    pub fn with_file(
+        file: impl Into<PathBuf>,
+        script: Script,
+        variables: Variables,
+    ) -> Self {
+        Self {
+            script,
+            file: file.into(),
+            variables,
+            scene: Scene::default(),
+
+            pending_choice: None,
+            random: Box::new(XorShift::from_entropy()),
+            missing_variables: MissingVariable::default(),
+        }
+    }
+
+    /// Draw values of the `random` commands from the
+    /// `random` source instead of the system seeded one
+    pub fn with_random(
+        mut self,
+        random: impl RandomSource + 'static,
+    ) -> Self {
+        self.random = Box::new(random);
+        self
+    }
+
+    /// Show the choice options which are not set variables
+    /// according to `missing` instead of the placeholder
+    pub fn with_missing_variables(
+        mut self,
+        missing: MissingVariable,
+    ) -> Self {
+        self.missing_variables = missing;
+        self
+    }
+
+    /// Create interpreter of the [`MAIN_SCRIPT`]
+    pub fn with_variables(script: Script, variables: Variables) -> Self {
+        Self::with_file(MAIN_SCRIPT, script, variables)
+    }
+
+    pub fn new(script: Script) -> Self {
+        Self::with_variables(script, Variables::default())
+    }
+}
@@ -1,8 +1,27 @@
 pub mod error;
+pub mod global;
+pub mod graph;
+pub mod img;
 pub mod index;
+pub mod interpreter;
 pub mod novel;
+pub mod playthrough;
+pub mod random;
+pub mod resource;
+pub mod routes;
+pub mod runtime;
+pub mod save;
 pub mod script;
+pub mod validate;
+pub mod variables;
+pub mod vnds;
 
 pub mod info;
 
+mod ini;
+mod xml;
+
 pub use nds_parser as parser;
+
+#[cfg(test)]
+mod tests;
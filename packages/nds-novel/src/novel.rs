@@ -4,15 +4,20 @@ use {
             LoadScriptError,
             NovelLoadError,
         },
+        img::ImgIni,
+        index::NovelIndex,
+        info::NovelInfo,
+        resource::{
+            self,
+            Resource,
+            ResourceSource,
+        },
         script::Script,
     },
     nds_parser::parser::ParseScript,
     std::{
-        fs,
-        path::{
-            Path,
-            PathBuf,
-        },
+        path::Path,
+        sync::Arc,
     },
 };
 
@@ -24,13 +29,9 @@ macro_rules! resource_delegates {
             pub fn $name(
                 &self,
                 path: impl core::convert::AsRef<std::path::Path>,
-            ) -> Result<PathBuf, $crate::error::ResourceLoadError> {
-                let path = self.resources.$name.join(path);
-                if path.exists() {
-                    Ok(path)
-                } else {
-                    Err($crate::error::ResourceLoadError::FileNotFound)
-                }
+            ) -> Result<Resource, $crate::error::ResourceLoadError> {
+                Resource::lookup(&self.resources.$name, path)
+                    .ok_or($crate::error::ResourceLoadError::FileNotFound)
             }
         )*
     };
@@ -38,151 +39,143 @@ macro_rules! resource_delegates {
 
 #[derive(Debug, Clone)]
 pub struct NovelResources {
-    pub background: PathBuf,
-    pub foreground: PathBuf,
-    pub script: PathBuf,
-    pub sound: PathBuf,
+    pub root: Arc<dyn ResourceSource>,
+
+    pub background: Arc<dyn ResourceSource>,
+    pub foreground: Arc<dyn ResourceSource>,
+    pub script: Arc<dyn ResourceSource>,
+    pub sound: Arc<dyn ResourceSource>,
 
     pub icon: NovelIcon,
 }
 
 #[derive(Debug, Clone)]
 pub struct NovelIcon {
-    pub high: PathBuf,
-    pub low: PathBuf,
+    pub high: Resource,
+    pub low: Resource,
 
-    pub thumbnail: PathBuf,
+    pub thumbnail: Resource,
 }
 
 #[derive(Debug, Clone)]
 pub struct Novel {
-    pub title: String,
+    pub info: NovelInfo,
     pub resources: NovelResources,
-    pub device_resolution: (u16, u16),
-    // TODO: Implement index, possibly polonius-the-crab can solve my
-    // issue, but not today. Fuck NLL.
+    pub img: ImgIni,
+
+    /// Index of all novel scripts built at the load time
+    pub index: NovelIndex,
 }
 
 impl Novel {
+    /// Load novel from the directory or from the zip
+    /// archive. Every resource directory may be
+    /// replaced with the `<name>.zip` archive
     pub fn try_load(
         path: impl AsRef<Path>,
+    ) -> Result<Self, NovelLoadError> {
+        let path = path.as_ref();
+        let root = resource::open_path(path).map_err(|_| {
+            NovelLoadError::InvalidArchive(path.to_owned())
+        })?;
+
+        Self::try_load_from(root)
+    }
+
+    /// Load novel from the arbitrary resource source
+    pub fn try_load_from(
+        root: Arc<dyn ResourceSource>,
     ) -> Result<Self, NovelLoadError> {
         fn try_create_resource(
-            path: impl AsRef<Path>,
+            root: &Arc<dyn ResourceSource>,
             next: impl AsRef<Path>,
             error: NovelLoadError,
-        ) -> Result<PathBuf, NovelLoadError> {
-            let joined = path.as_ref().join(next);
-            if joined.exists() {
-                Ok(joined)
-            } else {
-                Err(error)
-            }
+        ) -> Result<Resource, NovelLoadError> {
+            Resource::lookup(root, next).ok_or(error)
+        }
+
+        fn try_open_directory(
+            root: &Arc<dyn ResourceSource>,
+            name: &str,
+            error: NovelLoadError,
+        ) -> Result<Arc<dyn ResourceSource>, NovelLoadError> {
+            root.subdirectory(name)
+                .map_err(|_| {
+                    NovelLoadError::InvalidArchive(
+                        format!("{name}.zip").into(),
+                    )
+                })?
+                .ok_or(error)
         }
 
         fn try_load_info(
-            path: impl AsRef<Path>,
-        ) -> Result<String, NovelLoadError> {
-            let info = try_create_resource(
-                path,
-                "info.txt",
-                NovelLoadError::NoTitle,
-            )?;
-
-            fs::read_to_string(info)
-                .map_err(|_| NovelLoadError::NoTitle)?
-                .lines()
-                .filter_map(|l| l.split_once('='))
-                .map(|(_, v)| v.to_owned())
-                .next()
-                .ok_or(NovelLoadError::NoTitle)
+            root: &Arc<dyn ResourceSource>,
+        ) -> Result<NovelInfo, NovelLoadError> {
+            let bytes = root
+                .read(Path::new("info.txt"))
+                .map_err(|_| NovelLoadError::NoInfo)?;
+
+            NovelInfo::from_bytes(&bytes).map_err(Into::into)
         }
 
         fn try_load_img(
-            path: impl AsRef<Path>,
-        ) -> Result<(u16, u16), NovelLoadError> {
-            let img = try_create_resource(
-                path,
-                "img.ini",
-                NovelLoadError::InvalidImgIni,
-            )?;
-            let text = fs::read_to_string(img)
-                .map_err(|_| NovelLoadError::InvalidImgIni)?;
-
-            let vals: Vec<(&str, u16)> = text
-                .lines()
-                .map(str::trim)
-                .filter(|i| !i.is_empty())
-                .filter_map(|line| line.split_once('='))
-                .filter_map(|(key, value)| {
-                    if let Ok(v) = value.parse::<u16>() {
-                        Some((key, v))
-                    } else {
-                        None
-                    }
-                })
-                .collect();
-
-            let size = vals
-                .iter()
-                .find(|&&(k, _)| k == "height")
-                .zip(vals.iter().find(|&&(k, _)| k == "width"));
-
-            size.map(
-                |(&(k1, v1), &(_, v2))| {
-                    if k1 == "width" {
-                        (v1, v2)
-                    } else {
-                        (v2, v1)
-                    }
-                },
-            )
-            .ok_or(NovelLoadError::InvalidImgIni)
+            root: &Arc<dyn ResourceSource>,
+        ) -> Result<ImgIni, NovelLoadError> {
+            let bytes = root
+                .read(Path::new("img.ini"))
+                .map_err(|_| NovelLoadError::NoImgIni)?;
+
+            ImgIni::from_bytes(&bytes).map_err(Into::into)
         }
 
-        let background = try_create_resource(
-            &path,
+        let background = try_open_directory(
+            &root,
             "background",
             NovelLoadError::NoBackgroundDirectory,
         )?;
-        let foreground = try_create_resource(
-            &path,
+        let foreground = try_open_directory(
+            &root,
             "foreground",
             NovelLoadError::NoForegroundDirectory,
         )?;
-        let script = try_create_resource(
-            &path,
+        let script = try_open_directory(
+            &root,
             "script",
             NovelLoadError::NoScriptDirectory,
         )?;
-        let sound = try_create_resource(
-            &path,
+        let sound = try_open_directory(
+            &root,
             "sound",
             NovelLoadError::NoSoundDirectory,
         )?;
 
         let icon = NovelIcon {
             high: try_create_resource(
-                &path,
+                &root,
                 "icon-high.png",
                 NovelLoadError::NoHighResolutionIcon,
             )?,
             low: try_create_resource(
-                &path,
+                &root,
                 "icon.png",
                 NovelLoadError::NoLowResolutionIcon,
             )?,
             thumbnail: try_create_resource(
-                &path,
+                &root,
                 "thumbnail.png",
                 NovelLoadError::NoThumbnail,
             )?,
         };
 
+        let index = NovelIndex::build(script.as_ref())
+            .map_err(|_| NovelLoadError::InvalidScriptDirectory)?;
+
         Ok(Self {
-            title: try_load_info(&path)?,
-            device_resolution: try_load_img(path)?,
+            index,
+            info: try_load_info(&root)?,
+            img: try_load_img(&root)?,
             resources: NovelResources {
+                root,
                 background,
                 foreground,
                 script,
@@ -196,13 +189,25 @@ impl Novel {
 impl Novel {
     resource_delegates!(background, foreground, script, sound);
 
+    /// Resolution of the images the novel was made for
+    pub const fn device_resolution(&self) -> (u16, u16) {
+        self.img.resolution()
+    }
+
     pub fn try_load_script(
         &self,
         path: impl AsRef<Path>,
     ) -> Result<Script, LoadScriptError> {
-        fs::read_to_string(self.resources.script.join(path))?
-            .parse_script()
-            .map(Script::new)
-            .map_err(|e| e.into())
+        let path = path.as_ref();
+
+        self.resources
+            .script
+            .read_to_string(path)?
+            .parse_script_spanned()
+            .map(Script::from_spanned)
+            .map_err(|error| LoadScriptError::Parse {
+                path: Path::new("script").join(path),
+                error,
+            })
     }
 }
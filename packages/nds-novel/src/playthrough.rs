@@ -0,0 +1,301 @@
+use {
+    crate::{
+        error::{
+            LoadScriptError,
+            PlaythroughError,
+            RuntimeError,
+        },
+        interpreter::{
+            Event,
+            Interpreter,
+            MAIN_SCRIPT,
+        },
+        novel::Novel,
+        random::{
+            RandomSource,
+            XorShift,
+        },
+        runtime::{
+            CallSite,
+            Runtime,
+        },
+    },
+    nds_parser::{
+        command::{
+            ChoiceOption,
+            Command,
+        },
+        error::RenderError,
+        text::{
+            MissingVariable,
+            Text,
+            TextSpan,
+            TextType,
+        },
+    },
+    std::fmt,
+};
+
+/// Limit of events in one playthrough, stops the novels
+/// which loop forever
+pub const DEFAULT_MAX_EVENTS: usize = 100_000;
+
+/// How the playthrough answers choices
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChoicePolicy {
+    /// Always pick the first option
+    First,
+
+    /// Always pick the last option
+    Last,
+
+    /// Pick options by their 0-based indices in the order
+    /// of choices, running out of them is an error
+    Scripted(Vec<usize>),
+}
+
+/// Presentation event with the command which caused it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranscriptEntry {
+    pub call_site: CallSite,
+
+    /// Event with the variables of the text rendered
+    pub event: Event,
+
+    /// 0-based index of the option picked for the choice
+    pub choice: Option<usize>,
+}
+
+/// Record of every presentation event of the playthrough.
+/// Formats as one line per event, suitable for diffing
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Transcript {
+    pub entries: Vec<TranscriptEntry>,
+}
+
+/// Plays the novel without the player, choices are answered
+/// by the [`ChoicePolicy`]
+#[derive(Debug)]
+pub struct Playthrough<'a> {
+    runtime: Runtime<'a>,
+    policy: ChoicePolicy,
+    transcript: Transcript,
+
+    /// Number of choices answered so far
+    choices: usize,
+
+    /// See [`DEFAULT_MAX_EVENTS`]
+    pub max_events: usize,
+
+    /// How the variables of the text and the choices which
+    /// are not set are rendered
+    pub missing_variables: MissingVariable,
+}
+
+impl<'a> Playthrough<'a> {
+    /// Start from the [`MAIN_SCRIPT`], the `random` command
+    /// draws values from the generator with the fixed seed
+    /// so the runs are reproducible
+    pub fn new(
+        novel: &'a Novel,
+        policy: ChoicePolicy,
+    ) -> Result<Self, LoadScriptError> {
+        Self::with_random(novel, policy, XorShift::seeded(0))
+    }
+
+    pub fn with_random(
+        novel: &'a Novel,
+        policy: ChoicePolicy,
+        random: impl RandomSource + 'static,
+    ) -> Result<Self, LoadScriptError> {
+        let script = novel.try_load_script(MAIN_SCRIPT)?;
+        let interpreter = Interpreter::new(script).with_random(random);
+
+        Ok(Self {
+            runtime: Runtime::with_interpreter(novel, interpreter),
+            policy,
+            transcript: Transcript::default(),
+            choices: 0,
+            max_events: DEFAULT_MAX_EVENTS,
+            missing_variables: MissingVariable::default(),
+        })
+    }
+
+    /// Play until the end of the novel. Transcript of the
+    /// events before the error is still available
+    pub fn run(&mut self) -> Result<(), PlaythroughError> {
+        self.runtime
+            .interpreter_mut()
+            .set_missing_variables(self.missing_variables);
+
+        loop {
+            if self.transcript.entries.len() >= self.max_events {
+                return Err(PlaythroughError::TooManyEvents(
+                    self.max_events,
+                ));
+            }
+
+            let event = self.runtime.step()?;
+            let call_site = self.runtime.call_site();
+            let event = match event {
+                Event::Text(text) => {
+                    Event::Text(self.render(&text).map_err(|error| {
+                        PlaythroughError::Render {
+                            call_site: call_site.clone(),
+                            error,
+                        }
+                    })?)
+                }
+                event => event,
+            };
+
+            let choice = match &event {
+                Event::Choice { options } => {
+                    let option = self.answer(&call_site, options.len())?;
+                    self.runtime
+                        .choose(option)
+                        .map_err(RuntimeError::from)?;
+                    Some(option)
+                }
+
+                _ => None,
+            };
+
+            let finished = event == Event::Finished;
+            self.transcript.entries.push(TranscriptEntry {
+                call_site,
+                event,
+                choice,
+            });
+            if finished {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Text with the variables replaced by their values
+    fn render(&self, text: &Text) -> Result<Text, RenderError> {
+        let Text::Spans {
+            click_to_advance, ..
+        } = text
+        else {
+            return Ok(text.clone());
+        };
+
+        let spans = text
+            .render(
+                self.runtime.interpreter().variables(),
+                self.missing_variables,
+            )?
+            .into_iter()
+            .map(|span| TextSpan {
+                text: TextType::Plain(span.text),
+                style: span.style,
+            })
+            .collect();
+
+        Ok(Text::Spans {
+            spans,
+            click_to_advance: *click_to_advance,
+        })
+    }
+
+    /// Pick the option of the choice with `count` options
+    fn answer(
+        &mut self,
+        call_site: &CallSite,
+        count: usize,
+    ) -> Result<usize, PlaythroughError> {
+        let number = self.choices;
+        self.choices += 1;
+
+        let option = match &self.policy {
+            ChoicePolicy::First => 0,
+            ChoicePolicy::Last => count.saturating_sub(1),
+            ChoicePolicy::Scripted(options) => *options
+                .get(number)
+                .ok_or_else(|| PlaythroughError::ChoicesExhausted {
+                    call_site: call_site.clone(),
+                    number,
+                })?,
+        };
+
+        if option >= count {
+            return Err(PlaythroughError::ChoiceOutOfRange {
+                call_site: call_site.clone(),
+                option,
+                count,
+            });
+        }
+
+        Ok(option)
+    }
+}
+
+impl<'a> Playthrough<'a> {
+    pub fn runtime(&self) -> &Runtime<'a> {
+        &self.runtime
+    }
+
+    pub fn transcript(&self) -> &Transcript {
+        &self.transcript
+    }
+
+    pub fn into_transcript(self) -> Transcript {
+        self.transcript
+    }
+}
+
+/// Formats as `<call site>: <event>`, events are printed
+/// as the commands which caused them. The picked option is
+/// appended to the choice as `-> <number>`
+impl fmt::Display for TranscriptEntry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: ", self.call_site)?;
+
+        let command = match &self.event {
+            Event::Text(text) => Command::Text(text.clone()),
+            Event::ClearText(ty) => Command::ClearText(*ty),
+            Event::Background { file, fadetime } => Command::BgLoad {
+                file: file.clone(),
+                fadetime: *fadetime,
+            },
+            Event::Foreground { file, coordinates } => Command::SetImg {
+                file: file.clone(),
+                coordinates: *coordinates,
+            },
+            Event::Sound(looping) => Command::Sound(looping.clone()),
+            Event::Music(file) => Command::Music { file: file.clone() },
+            Event::Choice { options } => Command::Choice {
+                options: options
+                    .iter()
+                    .cloned()
+                    .map(ChoiceOption::Option)
+                    .collect(),
+            },
+            Event::Delay { frames } => Command::Delay { frames: *frames },
+            Event::Jump { file, label } => Command::Jump {
+                file: file.clone(),
+                label: label.clone(),
+            },
+            Event::Finished => return f.write_str("end"),
+        };
+        write!(f, "{command}")?;
+
+        if let Some(choice) = self.choice {
+            write!(f, " -> {}", choice + 1)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl fmt::Display for Transcript {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in &self.entries {
+            writeln!(f, "{entry}")?;
+        }
+
+        Ok(())
+    }
+}
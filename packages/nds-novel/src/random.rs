@@ -0,0 +1,134 @@
+use std::{
+    collections::hash_map::RandomState,
+    fmt::Debug,
+    hash::{
+        BuildHasher,
+        Hasher,
+    },
+    ops::RangeInclusive,
+};
+
+/// Source of values for the `random` command. State of the
+/// source is stored in saves, so restored game draws the
+/// same values
+pub trait RandomSource: Debug + Send + Sync {
+    /// Draw value from the `range`
+    fn next_in(&mut self, range: RangeInclusive<u16>) -> u16;
+
+    /// Current state, restoring it with
+    /// [`RandomSource::set_state`] repeats the following
+    /// values
+    fn state(&self) -> u64;
+
+    fn set_state(&mut self, state: u64);
+
+    fn clone_box(&self) -> Box<dyn RandomSource>;
+}
+
+/// xorshift64* generator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct XorShift {
+    state: u64,
+}
+
+/// Replays the fixed values in a loop, values out of the
+/// requested range are clamped. Intended for tests
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Sequence {
+    values: Vec<u16>,
+    position: usize,
+}
+
+impl XorShift {
+    /// Generator which always produces the same values for
+    /// the same `seed`
+    pub const fn seeded(seed: u64) -> Self {
+        // splitmix64 step, spreads small seeds and never
+        // gives zero state for them
+        let mut z = seed.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+
+        Self {
+            state: (z ^ (z >> 31)) | 1,
+        }
+    }
+
+    /// Generator seeded from the system randomness
+    pub fn from_entropy() -> Self {
+        Self::seeded(RandomState::new().build_hasher().finish())
+    }
+}
+
+impl RandomSource for XorShift {
+    fn next_in(&mut self, range: RangeInclusive<u16>) -> u16 {
+        let (low, high) = (*range.start(), *range.end());
+        if low >= high {
+            return low;
+        }
+
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        let value = self.state.wrapping_mul(0x2545_f491_4f6c_dd1d);
+
+        low + (value % (u64::from(high - low) + 1)) as u16
+    }
+
+    fn state(&self) -> u64 {
+        self.state
+    }
+
+    fn set_state(&mut self, state: u64) {
+        // zero state is a fixed point of the xorshift
+        self.state = if state == 0 {
+            Self::seeded(0).state
+        } else {
+            state
+        };
+    }
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        Box::new(*self)
+    }
+}
+
+impl Sequence {
+    pub fn new(values: impl Into<Vec<u16>>) -> Self {
+        Self {
+            values: values.into(),
+            position: 0,
+        }
+    }
+}
+
+impl RandomSource for Sequence {
+    fn next_in(&mut self, range: RangeInclusive<u16>) -> u16 {
+        if self.values.is_empty() || range.is_empty() {
+            return *range.start();
+        }
+
+        let value = self.values[self.position % self.values.len()];
+        self.position += 1;
+
+        value.clamp(*range.start(), *range.end())
+    }
+
+    fn state(&self) -> u64 {
+        self.position as u64
+    }
+
+    fn set_state(&mut self, state: u64) {
+        self.position = state as usize;
+    }
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}
+
+impl Clone for Box<dyn RandomSource> {
+    fn clone(&self) -> Self {
+        self.clone_box()
+    }
+}
@@ -0,0 +1,535 @@
+use {
+    std::{
+        collections::BTreeMap,
+        fmt,
+        fs::{
+            self,
+            File,
+        },
+        io::{
+            self,
+            Cursor,
+            Read,
+            Seek,
+        },
+        path::{
+            Component,
+            Path,
+            PathBuf,
+        },
+        sync::{
+            Arc,
+            Mutex,
+        },
+    },
+    zip::ZipArchive,
+};
+
+pub trait ReadSeek: Read + Seek + Send {}
+
+impl<T: Read + Seek + Send> ReadSeek for T {}
+
+/// Virtual filesystem the novel resources are loaded from.
+/// All paths are relative to the source root, absolute
+/// paths and paths with `..` are rejected
+pub trait ResourceSource: fmt::Debug + Send + Sync {
+    /// Whether the file exists
+    fn exists(&self, path: &Path) -> bool;
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>>;
+
+    /// Paths of all files inside the source
+    fn files(&self) -> io::Result<Vec<PathBuf>>;
+
+    /// Get nested source by the directory name. The
+    /// directory is looked up as is first and as a
+    /// `<name>.zip` archive afterwards
+    fn subdirectory(
+        &self,
+        name: &str,
+    ) -> io::Result<Option<Arc<dyn ResourceSource>>>;
+
+    /// Open file for the random access
+    fn open(&self, path: &Path) -> io::Result<Box<dyn ReadSeek>> {
+        Ok(Box::new(Cursor::new(self.read(path)?)))
+    }
+
+    /// Path of the file on the filesystem, `None` if the
+    /// file is not stored there
+    fn filesystem_path(&self, _path: &Path) -> Option<PathBuf> {
+        None
+    }
+
+    fn read_to_string(&self, path: &Path) -> io::Result<String> {
+        String::from_utf8(self.read(path)?)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Plain directory on the filesystem
+#[derive(Debug, Clone)]
+pub struct DirectorySource {
+    root: PathBuf,
+}
+
+/// Opened zip archive shared between the sources
+pub struct Archive {
+    /// Archive location, used only for debugging purposes
+    origin: PathBuf,
+    zip: Mutex<ZipArchive<Box<dyn ReadSeek>>>,
+}
+
+/// Directory inside the zip archive
+#[derive(Debug, Clone)]
+pub struct ZipSource {
+    archive: Arc<Archive>,
+
+    /// Path of the directory inside the archive, empty or
+    /// ends with `/`
+    prefix: String,
+}
+
+/// Files stored in memory
+#[derive(Debug, Clone, Default)]
+pub struct MemorySource {
+    files: BTreeMap<PathBuf, Vec<u8>>,
+}
+
+/// Stack of the sources, files from the upper layers shadow
+/// files from the lower ones
+#[derive(Debug, Clone)]
+pub struct OverlaySource {
+    /// Layers from the top to the bottom
+    layers: Vec<Arc<dyn ResourceSource>>,
+}
+
+/// Single resource file inside the [`ResourceSource`]
+#[derive(Debug, Clone)]
+pub struct Resource {
+    pub source: Arc<dyn ResourceSource>,
+    pub path: PathBuf,
+}
+
+/// Open novel directory or zip archive
+pub fn open_path(
+    path: impl AsRef<Path>,
+) -> io::Result<Arc<dyn ResourceSource>> {
+    let path = path.as_ref();
+    Ok(if path.is_file() {
+        Arc::new(ZipSource::open_novel(path)?)
+    } else {
+        Arc::new(DirectorySource::new(path))
+    })
+}
+
+impl DirectorySource {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, path: &Path) -> io::Result<PathBuf> {
+        Ok(self.root.join(relative_path(path)?))
+    }
+}
+
+impl ResourceSource for DirectorySource {
+    fn exists(&self, path: &Path) -> bool {
+        self.path(path).is_ok_and(|path| path.is_file())
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        fs::read(self.path(path)?)
+    }
+
+    fn files(&self) -> io::Result<Vec<PathBuf>> {
+        fn walk(
+            root: &Path,
+            directory: &Path,
+            files: &mut Vec<PathBuf>,
+        ) -> io::Result<()> {
+            for entry in fs::read_dir(root.join(directory))? {
+                let entry = entry?;
+                let path = directory.join(entry.file_name());
+
+                if entry.file_type()?.is_dir() {
+                    walk(root, &path, files)?;
+                } else {
+                    files.push(path);
+                }
+            }
+
+            Ok(())
+        }
+
+        let mut files = Vec::new();
+        walk(&self.root, Path::new(""), &mut files)?;
+        files.sort();
+
+        Ok(files)
+    }
+
+    fn subdirectory(
+        &self,
+        name: &str,
+    ) -> io::Result<Option<Arc<dyn ResourceSource>>> {
+        let directory = self.path(Path::new(name))?;
+        if directory.is_dir() {
+            return Ok(Some(Arc::new(Self::new(directory))));
+        }
+
+        match Archive::open(directory.with_extension("zip")) {
+            Ok(archive) => {
+                Ok(Some(Arc::new(ZipSource::named(archive, name))))
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn ReadSeek>> {
+        Ok(Box::new(File::open(self.path(path)?)?))
+    }
+
+    fn filesystem_path(&self, path: &Path) -> Option<PathBuf> {
+        self.path(path).ok()
+    }
+}
+
+impl Archive {
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref();
+        Self::from_reader(path, Box::new(File::open(path)?))
+    }
+
+    pub fn from_reader(
+        origin: impl Into<PathBuf>,
+        reader: Box<dyn ReadSeek>,
+    ) -> io::Result<Self> {
+        Ok(Self {
+            origin: origin.into(),
+            zip: Mutex::new(ZipArchive::new(reader)?),
+        })
+    }
+
+    /// Whether any file name satisfies `predicate`
+    fn any_name(&self, predicate: impl Fn(&str) -> bool) -> bool {
+        self.zip
+            .lock()
+            .unwrap()
+            .file_names()
+            .any(predicate)
+    }
+
+    fn read(&self, name: &str) -> io::Result<Vec<u8>> {
+        let mut zip = self.zip.lock().unwrap();
+        let mut file = zip
+            .by_name(name)
+            .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e))?;
+
+        let mut buffer = Vec::with_capacity(file.size() as usize);
+        file.read_to_end(&mut buffer)?;
+
+        Ok(buffer)
+    }
+}
+
+impl ZipSource {
+    /// Open zip archive with the whole novel. The novel
+    /// root is the directory containing `info.txt`, so
+    /// the archive may have the novel either at the top
+    /// level or inside a directory
+    pub fn open_novel(path: impl AsRef<Path>) -> io::Result<Self> {
+        let archive = Archive::open(path)?;
+        let prefix = archive
+            .zip
+            .lock()
+            .unwrap()
+            .file_names()
+            .filter_map(|name| name.strip_suffix("info.txt"))
+            .filter(|prefix| prefix.is_empty() || prefix.ends_with('/'))
+            .min_by_key(|prefix| prefix.len())
+            .unwrap_or_default()
+            .to_owned();
+
+        Ok(Self {
+            archive: Arc::new(archive),
+            prefix,
+        })
+    }
+
+    /// Use archive of the resources named `name`, the
+    /// archive may store them either at the top level
+    /// or inside the `name` directory
+    pub fn named(archive: Archive, name: &str) -> Self {
+        let directory = format!("{name}/");
+        let prefix = if archive.any_name(|n| n.starts_with(&directory)) {
+            directory
+        } else {
+            String::new()
+        };
+
+        Self {
+            archive: Arc::new(archive),
+            prefix,
+        }
+    }
+
+    fn name(&self, path: &Path) -> io::Result<String> {
+        archive_name(&self.prefix, path)
+    }
+}
+
+impl ResourceSource for ZipSource {
+    fn exists(&self, path: &Path) -> bool {
+        self.name(path)
+            .is_ok_and(|name| self.archive.any_name(|n| n == name))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.archive.read(&self.name(path)?)
+    }
+
+    fn files(&self) -> io::Result<Vec<PathBuf>> {
+        Ok(self
+            .archive
+            .zip
+            .lock()
+            .unwrap()
+            .file_names()
+            .filter_map(|name| name.strip_prefix(&self.prefix))
+            .filter(|name| !name.is_empty() && !name.ends_with('/'))
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    fn subdirectory(
+        &self,
+        name: &str,
+    ) -> io::Result<Option<Arc<dyn ResourceSource>>> {
+        let directory = format!("{}/", self.name(Path::new(name))?);
+        if self
+            .archive
+            .any_name(|n| n.starts_with(&directory))
+        {
+            return Ok(Some(Arc::new(Self {
+                archive: Arc::clone(&self.archive),
+                prefix: directory,
+            })));
+        }
+
+        let zip_name = format!("{}{name}.zip", self.prefix);
+        if !self.archive.any_name(|n| n == zip_name) {
+            return Ok(None);
+        }
+
+        let archive = Archive::from_reader(
+            self.archive.origin.join(&zip_name),
+            Box::new(Cursor::new(self.archive.read(&zip_name)?)),
+        )?;
+        Ok(Some(Arc::new(Self::named(archive, name))))
+    }
+}
+
+impl MemorySource {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(
+        &mut self,
+        path: impl Into<PathBuf>,
+        contents: impl Into<Vec<u8>>,
+    ) {
+        self.files.insert(path.into(), contents.into());
+    }
+
+    pub fn with_file(
+        mut self,
+        path: impl Into<PathBuf>,
+        contents: impl Into<Vec<u8>>,
+    ) -> Self {
+        self.insert(path, contents);
+        self
+    }
+}
+
+impl ResourceSource for MemorySource {
+    fn exists(&self, path: &Path) -> bool {
+        relative_path(path).is_ok_and(|path| self.files.contains_key(path))
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.files
+            .get(relative_path(path)?)
+            .cloned()
+            .ok_or_else(|| not_found(path))
+    }
+
+    fn files(&self) -> io::Result<Vec<PathBuf>> {
+        Ok(self.files.keys().cloned().collect())
+    }
+
+    fn subdirectory(
+        &self,
+        name: &str,
+    ) -> io::Result<Option<Arc<dyn ResourceSource>>> {
+        relative_path(Path::new(name))?;
+        let nested: BTreeMap<_, _> = self
+            .files
+            .iter()
+            .filter_map(|(path, contents)| {
+                path.strip_prefix(name)
+                    .ok()
+                    .filter(|p| !p.as_os_str().is_empty())
+                    .map(|p| (p.to_owned(), contents.clone()))
+            })
+            .collect();
+        if !nested.is_empty() {
+            return Ok(Some(Arc::new(Self { files: nested })));
+        }
+
+        let zip_name = PathBuf::from(format!("{name}.zip"));
+        match self.files.get(&zip_name) {
+            Some(bytes) => {
+                let archive = Archive::from_reader(
+                    zip_name,
+                    Box::new(Cursor::new(bytes.clone())),
+                )?;
+                Ok(Some(Arc::new(ZipSource::named(archive, name))))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl OverlaySource {
+    /// Create overlay, `layers` are ordered from the top to
+    /// the bottom
+    pub fn new(layers: Vec<Arc<dyn ResourceSource>>) -> Self {
+        Self { layers }
+    }
+
+    fn layer_with(&self, path: &Path) -> io::Result<&dyn ResourceSource> {
+        self.layers
+            .iter()
+            .find(|layer| layer.exists(path))
+            .map(|layer| layer.as_ref())
+            .ok_or_else(|| not_found(path))
+    }
+}
+
+impl ResourceSource for OverlaySource {
+    fn exists(&self, path: &Path) -> bool {
+        self.layer_with(path).is_ok()
+    }
+
+    fn read(&self, path: &Path) -> io::Result<Vec<u8>> {
+        self.layer_with(path)?.read(path)
+    }
+
+    fn files(&self) -> io::Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        for layer in &self.layers {
+            files.extend(layer.files()?);
+        }
+        files.sort();
+        files.dedup();
+
+        Ok(files)
+    }
+
+    /// Subdirectories of the same name are overlaid too
+    fn subdirectory(
+        &self,
+        name: &str,
+    ) -> io::Result<Option<Arc<dyn ResourceSource>>> {
+        let mut layers = Vec::new();
+        for layer in &self.layers {
+            layers.extend(layer.subdirectory(name)?);
+        }
+
+        Ok(if layers.is_empty() {
+            None
+        } else {
+            Some(Arc::new(Self::new(layers)))
+        })
+    }
+
+    fn open(&self, path: &Path) -> io::Result<Box<dyn ReadSeek>> {
+        self.layer_with(path)?.open(path)
+    }
+
+    fn filesystem_path(&self, path: &Path) -> Option<PathBuf> {
+        self.layer_with(path).ok()?.filesystem_path(path)
+    }
+}
+
+impl Resource {
+    /// Get resource inside the `source` if it exists
+    pub fn lookup(
+        source: &Arc<dyn ResourceSource>,
+        path: impl AsRef<Path>,
+    ) -> Option<Self> {
+        let path = path.as_ref();
+        source.exists(path).then(|| Self {
+            source: Arc::clone(source),
+            path: path.to_owned(),
+        })
+    }
+
+    pub fn read(&self) -> io::Result<Vec<u8>> {
+        self.source.read(&self.path)
+    }
+
+    /// Path of the resource on the filesystem, `None` if
+    /// the resource is not stored there
+    pub fn filesystem_path(&self) -> Option<PathBuf> {
+        self.source.filesystem_path(&self.path)
+    }
+}
+
+impl fmt::Debug for Archive {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Archive")
+            .field("origin", &self.origin)
+            .finish_non_exhaustive()
+    }
+}
+
+fn not_found(path: &Path) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::NotFound,
+        format!("{} was not found", path.display()),
+    )
+}
+
+/// Check that `path` stays inside the source root
+fn relative_path(path: &Path) -> io::Result<&Path> {
+    let inside = path.components().all(|component| {
+        matches!(component, Component::Normal(..) | Component::CurDir)
+    });
+
+    if inside {
+        Ok(path)
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{} is outside of the source root", path.display()),
+        ))
+    }
+}
+
+/// Convert `path` to the zip entry name inside the `prefix`
+fn archive_name(prefix: &str, path: &Path) -> io::Result<String> {
+    let mut name = prefix.to_owned();
+    for component in relative_path(path)?.components() {
+        if let Component::Normal(part) = component {
+            if !name.is_empty() && !name.ends_with('/') {
+                name.push('/');
+            }
+            name.push_str(&part.to_string_lossy());
+        }
+    }
+
+    Ok(name)
+}
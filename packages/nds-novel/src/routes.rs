@@ -0,0 +1,456 @@
+use {
+    crate::{
+        error::{
+            LoadScriptError,
+            RuntimeError,
+        },
+        interpreter::{
+            Event,
+            Interpreter,
+            Variables,
+            MAIN_SCRIPT,
+        },
+        novel::Novel,
+        random::RandomSource,
+        runtime::{
+            CallSite,
+            Runtime,
+        },
+    },
+    nds_parser::{
+        command::Command,
+        span::Span,
+    },
+    std::{
+        collections::{
+            BTreeMap,
+            BTreeSet,
+            HashSet,
+            VecDeque,
+        },
+        fmt,
+        mem,
+        ops::RangeInclusive,
+        path::PathBuf,
+        sync::{
+            Arc,
+            Mutex,
+        },
+    },
+};
+
+/// Limit of distinct states at choices, see
+/// [`Explorer::max_states`]
+pub const DEFAULT_MAX_STATES: usize = 10_000;
+
+/// Limit of events between two choices, stops the loops
+/// which never reach a choice
+pub const DEFAULT_MAX_EVENTS: usize = 100_000;
+
+/// Option picked on the way to the ending
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChoiceStep {
+    pub call_site: CallSite,
+
+    /// 0-based index of the option
+    pub option: usize,
+    pub text: String,
+}
+
+/// Sequence of the options picked from the start of the
+/// story
+pub type ChoicePath = Vec<ChoiceStep>;
+
+/// Place where the story finishes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ending {
+    /// Script path relative to the script directory
+    pub file: PathBuf,
+
+    /// Last label before the final command of the script
+    pub label: Option<String>,
+
+    /// Paths which reached the ending. Path which joins an
+    /// already explored state is not followed further, so
+    /// each state contributes the first path to it only
+    pub paths: Vec<ChoicePath>,
+}
+
+/// Choice option which was never picked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnreachedOption {
+    pub call_site: CallSite,
+
+    /// 0-based index of the option
+    pub option: usize,
+    pub text: String,
+}
+
+/// Path on which the story failed
+#[derive(Debug)]
+pub struct Failure {
+    pub path: ChoicePath,
+    pub error: RuntimeError,
+}
+
+#[derive(Debug, Default)]
+pub struct Exploration {
+    /// Endings sorted by their script and label
+    pub endings: Vec<Ending>,
+
+    /// Options of all choices of the novel which no path
+    /// has picked, including the choices which were never
+    /// shown
+    pub unreached: Vec<UnreachedOption>,
+
+    pub failures: Vec<Failure>,
+
+    /// Number of distinct states at choices and `random`
+    /// commands
+    pub states: usize,
+
+    /// Whether some paths were abandoned at the
+    /// [`Explorer::max_states`] limit
+    pub state_limit_reached: bool,
+
+    /// Whether some paths were abandoned after
+    /// [`Explorer::max_events`] events without a choice
+    pub event_limit_reached: bool,
+}
+
+impl Exploration {
+    /// Whether some paths were abandoned because of the
+    /// limits, in this case unreached options may be
+    /// reachable
+    pub const fn truncated(&self) -> bool {
+        self.state_limit_reached || self.event_limit_reached
+    }
+}
+
+/// Walks every choice path of the novel. Conditions are
+/// evaluated with the actual variable values and every
+/// value a `random` can produce is tried
+#[derive(Debug, Clone, Copy)]
+pub struct Explorer<'a> {
+    novel: &'a Novel,
+
+    /// Exploration stops after visiting this many distinct
+    /// states, state is the position of the choice or the
+    /// `random` command with all the variables
+    pub max_states: usize,
+
+    /// See [`DEFAULT_MAX_EVENTS`]
+    pub max_events: usize,
+}
+
+/// Story state at the choice or before the `random`, equal
+/// states lead to the same endings
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct State {
+    file: PathBuf,
+    cursor: usize,
+    variables: Variables,
+
+    /// Values of the `random` commands planned for the
+    /// step, `None` at the choice
+    random: Option<Vec<u16>>,
+}
+
+/// Path waiting to be explored
+struct Branch<'a> {
+    runtime: Runtime<'a>,
+    path: ChoicePath,
+
+    /// Events since the last choice
+    events: usize,
+    last_event: Option<CallSite>,
+
+    /// Values of the `random` commands of the next step
+    planned: Vec<u16>,
+}
+
+/// Random source of the explorer. The `planned` values are
+/// drawn first, the next draw returns the lowest value and
+/// records its range into `unplanned`, so the step can be
+/// repeated with every value of the range
+#[derive(Debug, Clone)]
+struct Branching {
+    planned: VecDeque<u16>,
+    unplanned: Arc<Mutex<Option<RangeInclusive<u16>>>>,
+}
+
+impl<'a> Explorer<'a> {
+    pub const fn new(novel: &'a Novel) -> Self {
+        Self {
+            novel,
+            max_states: DEFAULT_MAX_STATES,
+            max_events: DEFAULT_MAX_EVENTS,
+        }
+    }
+
+    /// Explore the story from the start of the
+    /// [`MAIN_SCRIPT`]
+    pub fn explore(&self) -> Result<Exploration, LoadScriptError> {
+        let script = self.novel.try_load_script(MAIN_SCRIPT)?;
+        let unplanned = Arc::default();
+        let interpreter =
+            Interpreter::new(script).with_random(Branching {
+                planned: VecDeque::new(),
+                unplanned: Arc::clone(&unplanned),
+            });
+
+        let mut exploration = Exploration::default();
+        let mut endings = BTreeMap::<_, Vec<ChoicePath>>::new();
+        let mut visited = HashSet::new();
+        let mut picked = BTreeSet::new();
+
+        let mut stack = vec![Branch {
+            runtime: Runtime::with_interpreter(self.novel, interpreter),
+            path: ChoicePath::new(),
+            events: 0,
+            last_event: None,
+            planned: Vec::new(),
+        }];
+        'paths: while let Some(branch) = stack.pop() {
+            let Branch {
+                mut runtime,
+                path,
+                mut events,
+                mut last_event,
+                mut planned,
+            } = branch;
+
+            while events < self.max_events {
+                events += 1;
+
+                let before = runtime.clone();
+                let result = runtime.step();
+
+                // the step is repeated for every value of the
+                // first `random` which was not planned
+                let range = unplanned.lock().unwrap().take();
+                let draws = mem::take(&mut planned);
+                if let Some(range) = range {
+                    let state = State::new(&before, Some(draws.clone()));
+                    if visited.contains(&state) {
+                        continue 'paths;
+                    }
+                    if visited.len() >= self.max_states {
+                        exploration.state_limit_reached = true;
+                        continue 'paths;
+                    }
+                    visited.insert(state);
+
+                    for value in range.rev() {
+                        let mut planned = draws.clone();
+                        planned.push(value);
+
+                        let mut runtime = before.clone();
+                        runtime.interpreter_mut().set_random(Branching {
+                            planned: planned.iter().copied().collect(),
+                            unplanned: Arc::clone(&unplanned),
+                        });
+
+                        stack.push(Branch {
+                            runtime,
+                            path: path.clone(),
+                            events: events - 1,
+                            last_event: last_event.clone(),
+                            planned,
+                        });
+                    }
+                    continue 'paths;
+                }
+
+                let event = match result {
+                    Ok(event) => event,
+                    Err(error) => {
+                        exploration.failures.push(Failure { path, error });
+                        continue 'paths;
+                    }
+                };
+
+                let options = match event {
+                    Event::Finished => {
+                        let file = runtime.interpreter().file().to_owned();
+                        let label = self.ending_label(&file, last_event);
+                        endings
+                            .entry((file, label))
+                            .or_default()
+                            .push(path);
+                        continue 'paths;
+                    }
+                    Event::Choice { options } => options,
+
+                    _ => {
+                        last_event = Some(runtime.call_site());
+                        continue;
+                    }
+                };
+
+                let state = State::new(&runtime, None);
+                if visited.contains(&state) {
+                    continue 'paths;
+                }
+                if visited.len() >= self.max_states {
+                    exploration.state_limit_reached = true;
+                    continue 'paths;
+                }
+                visited.insert(state);
+
+                let call_site = runtime.call_site();
+                for (option, text) in options.into_iter().enumerate().rev()
+                {
+                    let mut runtime = runtime.clone();
+                    runtime
+                        .choose(option)
+                        .expect("option is in range of the choice");
+
+                    if let Some(span) = call_site.span {
+                        picked.insert((
+                            call_site.file.clone(),
+                            span,
+                            option,
+                        ));
+                    }
+
+                    let mut path = path.clone();
+                    path.push(ChoiceStep {
+                        call_site: call_site.clone(),
+                        option,
+                        text,
+                    });
+                    stack.push(Branch {
+                        runtime,
+                        path,
+                        events: 0,
+                        last_event: None,
+                        planned: Vec::new(),
+                    });
+                }
+                continue 'paths;
+            }
+
+            exploration.event_limit_reached = true;
+        }
+
+        exploration.endings = endings
+            .into_iter()
+            .map(|((file, label), paths)| Ending { file, label, paths })
+            .collect();
+        exploration.unreached = self.unreached_options(&picked);
+        exploration.states = visited.len();
+
+        Ok(exploration)
+    }
+
+    /// Label before the command of the `last_event` in the
+    /// finished `file`
+    fn ending_label(
+        &self,
+        file: &PathBuf,
+        last_event: Option<CallSite>,
+    ) -> Option<String> {
+        let script = self.novel.index.script(file)?;
+        let end = match last_event {
+            Some(CallSite {
+                file: last_file,
+                span: Some(span),
+            }) if &last_file == file => script
+                .commands
+                .iter()
+                .position(|command| command.span == span)?,
+            _ => script.commands.len(),
+        };
+
+        script
+            .labels
+            .iter()
+            .filter(|(_, &index)| index <= end)
+            .max_by_key(|(_, &index)| index)
+            .map(|(label, _)| label.clone())
+    }
+
+    fn unreached_options(
+        &self,
+        picked: &BTreeSet<(PathBuf, Span, usize)>,
+    ) -> Vec<UnreachedOption> {
+        let mut unreached = Vec::new();
+        for script in self.novel.index.scripts() {
+            for command in &script.commands {
+                let Command::Choice { options } = &command.inner else {
+                    continue;
+                };
+
+                for (option, text) in options.iter().enumerate() {
+                    let key = (script.path.clone(), command.span, option);
+                    if !picked.contains(&key) {
+                        unreached.push(UnreachedOption {
+                            call_site: CallSite {
+                                file: script.path.clone(),
+                                span: Some(command.span),
+                            },
+                            option,
+                            text: text.to_string(),
+                        });
+                    }
+                }
+            }
+        }
+
+        unreached
+    }
+}
+
+impl State {
+    fn new(runtime: &Runtime<'_>, random: Option<Vec<u16>>) -> Self {
+        let interpreter = runtime.interpreter();
+
+        Self {
+            file: interpreter.file().to_owned(),
+            cursor: interpreter.script().cursor(),
+            variables: interpreter.variables().clone(),
+            random,
+        }
+    }
+}
+
+impl RandomSource for Branching {
+    fn next_in(&mut self, range: RangeInclusive<u16>) -> u16 {
+        if let Some(value) = self.planned.pop_front() {
+            return value;
+        }
+
+        let start = *range.start();
+        self.unplanned
+            .lock()
+            .unwrap()
+            .get_or_insert(range);
+        start
+    }
+
+    /// Draws depend on the explored branch only
+    fn state(&self) -> u64 {
+        0
+    }
+
+    fn set_state(&mut self, _state: u64) {}
+
+    fn clone_box(&self) -> Box<dyn RandomSource> {
+        Box::new(self.clone())
+    }
+}
+
+/// Formats as `<call site>: <number>) <text>`
+impl fmt::Display for ChoiceStep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}) {}", self.call_site, self.option + 1, self.text)
+    }
+}
+
+/// Formats as `<call site>: <number>) <text>`
+impl fmt::Display for UnreachedOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}) {}", self.call_site, self.option + 1, self.text)
+    }
+}
@@ -0,0 +1,172 @@
+use {
+    crate::{
+        error::{
+            InterpreterError,
+            LoadScriptError,
+            RuntimeError,
+        },
+        interpreter::{
+            Event,
+            Interpreter,
+            MAIN_SCRIPT,
+        },
+        novel::Novel,
+        script::Script,
+    },
+    nds_parser::span::Span,
+    std::{
+        collections::HashMap,
+        fmt,
+        path::{
+            Path,
+            PathBuf,
+        },
+    },
+};
+
+/// Location of the command in the novel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CallSite {
+    /// Script path relative to the script directory
+    pub file: PathBuf,
+
+    /// Location of the command, unknown if the script was
+    /// built without source locations
+    pub span: Option<Span>,
+}
+
+/// Interpreter of the whole novel, follows jumps between
+/// scripts. Loaded scripts are cached
+#[derive(Debug, Clone)]
+pub struct Runtime<'a> {
+    novel: &'a Novel,
+    interpreter: Interpreter,
+
+    /// Scripts with the cursor at the beginning, their
+    /// commands are shared with the interpreter and the
+    /// clones of the runtime
+    scripts: HashMap<PathBuf, Script>,
+}
+
+impl<'a> Runtime<'a> {
+    /// Start the novel from the [`MAIN_SCRIPT`]
+    pub fn new(novel: &'a Novel) -> Result<Self, LoadScriptError> {
+        let script = novel.try_load_script(MAIN_SCRIPT)?;
+
+        Ok(Self::with_interpreter(novel, Interpreter::new(script)))
+    }
+
+    /// Continue execution of the `interpreter`, for
+    /// example restored from the save
+    pub fn with_interpreter(
+        novel: &'a Novel,
+        interpreter: Interpreter,
+    ) -> Self {
+        Self {
+            novel,
+            interpreter,
+            scripts: HashMap::new(),
+        }
+    }
+
+    /// Run commands until the next presentation event.
+    /// Jumps are followed and are not reported, so the
+    /// interpreter can be saved after any step
+    pub fn step(&mut self) -> Result<Event, RuntimeError> {
+        loop {
+            match self.interpreter.step()? {
+                Event::Jump { file, label } => {
+                    self.jump(file, label.as_deref())?
+                }
+                event => return Ok(event),
+            }
+        }
+    }
+
+    /// Pass the option selected by the player, see
+    /// [`Interpreter::choose`]
+    pub fn choose(
+        &mut self,
+        option: usize,
+    ) -> Result<(), InterpreterError> {
+        self.interpreter.choose(option)
+    }
+
+    /// Continue execution in the `file` from the `label`
+    /// or from the beginning
+    pub fn jump(
+        &mut self,
+        file: PathBuf,
+        label: Option<&str>,
+    ) -> Result<(), RuntimeError> {
+        let call_site = self.call_site();
+        let script = match self.scripts.get(&file) {
+            Some(script) => script.clone(),
+            None => {
+                let script = self.novel.try_load_script(&file).map_err(
+                    |error| RuntimeError::LoadScript {
+                        call_site: call_site.clone(),
+                        file: file.clone(),
+                        error: Box::new(error),
+                    },
+                )?;
+
+                self.scripts
+                    .entry(file.clone())
+                    .or_insert(script)
+                    .clone()
+            }
+        };
+
+        self.interpreter
+            .load_script(&file, script, label)
+            .map_err(|_| RuntimeError::LabelNotFound {
+                call_site,
+                file,
+                label: label.unwrap_or_default().to_owned(),
+            })
+    }
+
+    /// Location of the last executed command
+    pub fn call_site(&self) -> CallSite {
+        let script = self.interpreter.script();
+
+        CallSite {
+            file: self.interpreter.file().to_owned(),
+            span: script
+                .cursor()
+                .checked_sub(1)
+                .and_then(|index| script.span(index)),
+        }
+    }
+}
+
+impl<'a> Runtime<'a> {
+    pub fn novel(&self) -> &'a Novel {
+        self.novel
+    }
+
+    pub fn interpreter(&self) -> &Interpreter {
+        &self.interpreter
+    }
+
+    pub fn interpreter_mut(&mut self) -> &mut Interpreter {
+        &mut self.interpreter
+    }
+
+    pub fn into_interpreter(self) -> Interpreter {
+        self.interpreter
+    }
+}
+
+/// Formats as `script/<path>:<line>:<column>`
+impl fmt::Display for CallSite {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", Path::new("script").join(&self.file).display())?;
+        if let Some(span) = self.span {
+            write!(f, ":{span}")?;
+        }
+
+        Ok(())
+    }
+}
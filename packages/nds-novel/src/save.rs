@@ -0,0 +1,245 @@
+use {
+    crate::{
+        error::{
+            RestoreSaveError,
+            SaveParseError,
+        },
+        interpreter::{
+            Interpreter,
+            Scene,
+            Variables,
+        },
+        novel::Novel,
+        random::{
+            RandomSource,
+            XorShift,
+        },
+    },
+    nds_parser::command::VariableStorageType,
+    std::{
+        collections::BTreeMap,
+        fmt,
+        path::PathBuf,
+        str::FromStr,
+    },
+};
+
+/// First word of every save file
+pub const SAVE_HEADER: &str = "vrs-save";
+
+/// Version of the save format written by [`SaveState`],
+/// saves of the earlier versions are still readable
+pub const SAVE_VERSION: u32 = 2;
+
+/// Runtime state of the game captured between commands.
+/// Global variables are not a part of the save, they are
+/// shared by all saves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveState {
+    /// Path of the current script relative to the script
+    /// directory
+    pub file: PathBuf,
+
+    /// Index of the next command to execute
+    pub cursor: usize,
+
+    pub variables: BTreeMap<String, u16>,
+    pub scene: Scene,
+
+    /// State of the random source, `None` in saves which
+    /// do not store it
+    pub random: Option<u64>,
+}
+
+impl SaveState {
+    /// Capture state of the interpreter. If the interpreter
+    /// waits for the choice, the choice will be asked again
+    /// after restoring. Finished script is saved at its end.
+    /// Jump which was reported by `Event::Jump` but not
+    /// loaded yet is lost, so capture the interpreter of the
+    /// `Runtime`, it follows jumps within the step
+    pub fn capture(interpreter: &Interpreter) -> Self {
+        let script = interpreter.script();
+        let mut cursor = script.cursor().min(script.len());
+        if interpreter.is_waiting_for_choice() {
+            cursor -= 1;
+        }
+
+        Self {
+            file: interpreter.file().to_owned(),
+            cursor,
+            variables: interpreter
+                .variables()
+                .storage(VariableStorageType::Local)
+                .clone(),
+            scene: interpreter.scene().clone(),
+            random: Some(interpreter.random().state()),
+        }
+    }
+
+    /// Load the saved script and resume interpreter at the
+    /// saved position. `variables` are used as the initial
+    /// variables, local ones are replaced by the saved
+    pub fn restore(
+        &self,
+        novel: &Novel,
+        variables: Variables,
+    ) -> Result<Interpreter, RestoreSaveError> {
+        self.restore_with(novel, variables, XorShift::from_entropy())
+    }
+
+    /// Same as [`SaveState::restore`] but with the custom
+    /// random source, saved random state is applied to it
+    pub fn restore_with(
+        &self,
+        novel: &Novel,
+        mut variables: Variables,
+        random: impl RandomSource + 'static,
+    ) -> Result<Interpreter, RestoreSaveError> {
+        let mut script = novel.try_load_script(&self.file)?;
+        if self.cursor > script.len() {
+            return Err(RestoreSaveError::CursorOutOfRange {
+                cursor: self.cursor,
+                len: script.len(),
+            });
+        }
+        script.adjust_cursor_to(self.cursor);
+
+        *variables.storage_mut(VariableStorageType::Local) =
+            self.variables.clone();
+
+        let mut interpreter =
+            Interpreter::with_file(&self.file, script, variables)
+                .with_random(random);
+        *interpreter.scene_mut() = self.scene.clone();
+        if let Some(state) = self.random {
+            interpreter.random_mut().set_state(state);
+        }
+
+        Ok(interpreter)
+    }
+}
+
+impl FromStr for SaveState {
+    type Err = SaveParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim_end_matches('\r')))
+            .filter(|(_, line)| !line.is_empty());
+
+        match lines
+            .next()
+            .and_then(|(_, line)| line.split_once(' '))
+        {
+            Some((SAVE_HEADER, version)) => {
+                if !version.parse().is_ok_and(|version| {
+                    (1..=SAVE_VERSION).contains(&version)
+                }) {
+                    return Err(SaveParseError::UnsupportedVersion(
+                        version.to_owned(),
+                    ));
+                }
+            }
+            _ => return Err(SaveParseError::NoHeader),
+        }
+
+        let mut file = None;
+        let mut cursor = None;
+        let mut variables = BTreeMap::new();
+        let mut scene = Scene::default();
+        let mut random = None;
+
+        for (line, text) in lines {
+            let malformed = || SaveParseError::MalformedLine {
+                line,
+                text: text.to_owned(),
+            };
+            let (key, value) =
+                text.split_once(' ').ok_or_else(malformed)?;
+
+            match key {
+                "file" => file = Some(PathBuf::from(value)),
+                "cursor" => {
+                    cursor = Some(value.parse().map_err(|_| malformed())?)
+                }
+                "background" => scene.background = Some(value.into()),
+                "foreground" => {
+                    let mut parts = value.splitn(3, ' ');
+                    let mut coordinate = || {
+                        parts
+                            .next()
+                            .and_then(|c| c.parse().ok())
+                            .ok_or_else(malformed)
+                    };
+                    let coordinates = (coordinate()?, coordinate()?);
+                    let file = parts.next().ok_or_else(malformed)?;
+
+                    scene.foreground.push((file.into(), coordinates));
+                }
+                "music" => scene.music = Some(value.into()),
+                "random" => {
+                    random = Some(value.parse().map_err(|_| malformed())?)
+                }
+                "var" => {
+                    let (name, value) = value
+                        .split_once(' ')
+                        .and_then(|(name, value)| {
+                            Some((name, value.parse().ok()?))
+                        })
+                        .ok_or_else(malformed)?;
+                    variables.insert(name.to_owned(), value);
+                }
+                "text" => {
+                    scene.history.push(value.parse().map_err(|error| {
+                        SaveParseError::Text { line, error }
+                    })?)
+                }
+
+                _ => return Err(malformed()),
+            }
+        }
+
+        Ok(Self {
+            file: file.ok_or(SaveParseError::MissingKey("file"))?,
+            cursor: cursor.ok_or(SaveParseError::MissingKey("cursor"))?,
+            variables,
+            scene,
+            random,
+        })
+    }
+}
+
+/// Writes the save in the current format version
+impl fmt::Display for SaveState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{SAVE_HEADER} {SAVE_VERSION}")?;
+        writeln!(f, "file {}", self.file.display())?;
+        writeln!(f, "cursor {}", self.cursor)?;
+
+        if let Some(background) = &self.scene.background {
+            writeln!(f, "background {}", background.display())?;
+        }
+        for (file, (x, y)) in &self.scene.foreground {
+            writeln!(f, "foreground {x} {y} {}", file.display())?;
+        }
+        if let Some(music) = &self.scene.music {
+            writeln!(f, "music {}", music.display())?;
+        }
+
+        if let Some(random) = self.random {
+            writeln!(f, "random {random}")?;
+        }
+
+        for (name, value) in &self.variables {
+            writeln!(f, "var {name} {value}")?;
+        }
+        for text in &self.scene.history {
+            writeln!(f, "text {text}")?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,7 +1,16 @@
 use {
     crate::error::JumpToLabelError,
-    nds_parser::command::Command,
-    std::collections::BTreeMap,
+    nds_parser::{
+        command::Command,
+        span::{
+            Span,
+            Spanned,
+        },
+    },
+    std::{
+        collections::BTreeMap,
+        sync::Arc,
+    },
 };
 
 #[derive(Debug, Clone)]
@@ -13,16 +22,27 @@ pub enum ScriptControlFlow<'a> {
     Stopped,
 }
 
+/// Script with the cursor, clones share the commands
 #[derive(Debug, Clone)]
 pub struct Script {
+    source: Arc<ScriptSource>,
+
+    /// Current script position
+    cursor: usize,
+}
+
+/// Parsed script which does not change during execution
+#[derive(Debug)]
+struct ScriptSource {
     /// Labels for fast lookup (goto & jumps)
     labels: BTreeMap<String, usize>,
 
     /// Script commands
     commands: Vec<Command>,
 
-    /// Current script position
-    cursor: usize,
+    /// Source locations of the commands, empty if the
+    /// script was built without them
+    spans: Vec<Span>,
 }
 
 impl Script {
@@ -32,7 +52,7 @@ impl Script {
     }
 
     pub fn peek_from(&self, from: usize) -> ScriptControlFlow<'_> {
-        if let Some(command) = self.commands.get(from) {
+        if let Some(command) = self.source.commands.get(from) {
             ScriptControlFlow::Execute(command)
         } else {
             ScriptControlFlow::Stopped
@@ -62,7 +82,7 @@ impl Script {
         &mut self,
         label: &str,
     ) -> Result<(), JumpToLabelError> {
-        if let Some(&cursor) = self.labels.get(label) {
+        if let Some(&cursor) = self.source.labels.get(label) {
             self.adjust_cursor_to(cursor);
 
             Ok(())
@@ -71,6 +91,20 @@ impl Script {
         }
     }
 
+    /// Get source location of the command at `index`
+    pub fn span(&self, index: usize) -> Option<Span> {
+        self.source.spans.get(index).copied()
+    }
+
+    /// Number of commands in the script
+    pub fn len(&self) -> usize {
+        self.source.commands.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.source.commands.is_empty()
+    }
+
     /// Get cursor position
     pub const fn cursor(&self) -> usize {
         self.cursor
@@ -94,18 +128,39 @@ impl Script {
         labels: BTreeMap<String, usize>,
     ) -> Self {
         Self {
-            labels,
-            commands,
+            source: Arc::new(ScriptSource {
+                labels,
+                commands,
+                spans: Vec::new(),
+            }),
 
             cursor: 0,
         }
     }
 
     pub fn new(commands: Vec<Command>) -> Self {
+        Self::with_spans(commands, Vec::new())
+    }
+
+    /// Create script preserving source locations of the
+    /// commands
+    pub fn from_spanned(commands: Vec<Spanned<Command>>) -> Self {
+        let (spans, commands) = commands
+            .into_iter()
+            .map(|Spanned { span, inner }| (span, inner))
+            .unzip();
+
+        Self::with_spans(commands, spans)
+    }
+
+    fn with_spans(commands: Vec<Command>, spans: Vec<Span>) -> Self {
         Self {
-            labels: Self::lookup_labels(&commands),
+            source: Arc::new(ScriptSource {
+                labels: Self::lookup_labels(&commands),
+                commands,
+                spans,
+            }),
 
-            commands,
             cursor: 0,
         }
     }
@@ -0,0 +1,1511 @@
+use {
+    crate::{
+        error::{
+            GlobalStoreError,
+            ImgIniError,
+            InfoParseError,
+            InterpreterError,
+            PlaythroughError,
+            RestoreSaveError,
+            RuntimeError,
+            SaveParseError,
+            VndsSaveError,
+        },
+        global::{
+            self,
+            GlobalStore,
+        },
+        graph::{
+            ControlFlowGraph,
+            EdgeKind,
+            Target,
+        },
+        img::{
+            ImgIni,
+            Rgb,
+            TextBox,
+        },
+        index::{
+            NovelIndex,
+            ResourceKind,
+        },
+        info::NovelInfo,
+        interpreter::{
+            Event,
+            Interpreter,
+            Variables,
+        },
+        novel::Novel,
+        playthrough::{
+            ChoicePolicy,
+            Playthrough,
+        },
+        random::{
+            RandomSource,
+            Sequence,
+            XorShift,
+        },
+        resource::{
+            Archive,
+            DirectorySource,
+            MemorySource,
+            OverlaySource,
+            ResourceSource,
+            ZipSource,
+        },
+        routes::Explorer,
+        runtime::Runtime,
+        save::SaveState,
+        script::{
+            Script,
+            ScriptControlFlow,
+        },
+        validate::{
+            self,
+            Problem,
+        },
+        variables::{
+            Access,
+            VariableAnalysis,
+            VariableStorage,
+        },
+        vnds::{
+            self,
+            VndsGlobal,
+            VndsSave,
+        },
+    },
+    nds_parser::prelude::{
+        Command,
+        MissingVariable,
+        ParseScript,
+        RenderError,
+        Severity,
+        Text,
+        TextType,
+        VariableStorageType,
+    },
+    std::{
+        env,
+        fs,
+        io::{
+            self,
+            Cursor,
+            Write,
+        },
+        path::{
+            Path,
+            PathBuf,
+        },
+        sync::Arc,
+    },
+    zip::{
+        write::FileOptions,
+        ZipWriter,
+    },
+};
+
+/// Files of the minimal valid novel
+const NOVEL_FILES: &[(&str, &str)] = &[
+    ("info.txt", "title=Test novel"),
+    ("img.ini", "width=256\nheight=192"),
+    ("icon.png", ""),
+    ("icon-high.png", ""),
+    ("thumbnail.png", ""),
+    ("script/main.scr", "bgload bg.jpg\njump other.scr"),
+    ("background/bg.jpg", "background"),
+    ("foreground/fg.png", "foreground"),
+    ("sound/music.aac", "music"),
+];
+
+fn memory_novel() -> MemorySource {
+    NOVEL_FILES
+        .iter()
+        .fold(MemorySource::new(), |source, (name, contents)| {
+            source.with_file(name, *contents)
+        })
+}
+
+/// Create empty directory for the test
+fn test_directory(name: &str) -> PathBuf {
+    let path = env::temp_dir()
+        .join(format!("nds-novel-{name}-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&path);
+    fs::create_dir_all(&path).unwrap();
+
+    path
+}
+
+fn zip_bytes<'a>(
+    files: impl IntoIterator<Item = (&'a str, &'a [u8])>,
+) -> Vec<u8> {
+    let mut zip = ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    for (name, contents) in files {
+        zip.start_file(name, FileOptions::default())
+            .unwrap();
+        zip.write_all(contents).unwrap();
+    }
+
+    zip.finish().unwrap().into_inner()
+}
+
+/// Write novel files, files from directories listed in
+/// `zipped` are packed into `<directory>.zip` archives
+fn write_novel(root: &Path, zipped: &[&str]) {
+    for (name, contents) in NOVEL_FILES {
+        let path = root.join(name);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+
+    for directory in zipped {
+        let prefix = format!("{directory}/");
+        let files = NOVEL_FILES
+            .iter()
+            .filter(|(name, _)| name.starts_with(&prefix))
+            .map(|(name, contents)| (*name, contents.as_bytes()));
+
+        fs::write(root.join(format!("{directory}.zip")), zip_bytes(files))
+            .unwrap();
+        fs::remove_dir_all(root.join(directory)).unwrap();
+    }
+}
+
+fn interpreter(source: &str) -> Interpreter {
+    Interpreter::new(Script::new(source.parse_script().unwrap()))
+}
+
+fn plain_text(event: Event) -> String {
+    match event {
+        Event::Text(Text::Spans { spans, .. }) => spans
+            .into_iter()
+            .map(|span| match span.text {
+                TextType::Plain(s) | TextType::Variable(s) => s,
+            })
+            .collect(),
+        e => panic!("Expected text event, got {e:?}"),
+    }
+}
+
+#[test]
+fn test_interpreter_setvar_and_if() {
+    let mut interpreter = interpreter(
+        "setvar a = 5\nsetvar a + 2\nif a == 7\ntext seven\nfi\nif a == \
+         5\ntext five\nfi\ntext end",
+    );
+
+    assert_eq!(plain_text(interpreter.step().unwrap()), "seven");
+    assert_eq!(plain_text(interpreter.step().unwrap()), "end");
+    assert_eq!(interpreter.step().unwrap(), Event::Finished);
+}
+
+#[test]
+fn test_interpreter_nested_if() {
+    let mut interpreter = interpreter(
+        "if a == 1\nif b == 0\ntext skipped\nfi\ntext skipped\nfi\ntext \
+         end",
+    );
+
+    assert_eq!(plain_text(interpreter.step().unwrap()), "end");
+}
+
+#[test]
+fn test_interpreter_choice() {
+    let mut interpreter = interpreter(
+        "setvar n = 3\nchoice yes|$n\nif selected == 2\ntext second\nfi",
+    );
+
+    assert_eq!(
+        interpreter.step().unwrap(),
+        Event::Choice {
+            options: vec!["yes".to_owned(), "3".to_owned()]
+        }
+    );
+    assert_eq!(
+        interpreter.step().unwrap_err(),
+        InterpreterError::ChoicePending
+    );
+    assert_eq!(
+        interpreter.choose(2).unwrap_err(),
+        InterpreterError::ChoiceOutOfRange {
+            option: 2,
+            count: 2
+        }
+    );
+
+    interpreter.choose(1).unwrap();
+    assert_eq!(plain_text(interpreter.step().unwrap()), "second");
+}
+
+#[test]
+fn test_interpreter_choice_missing_variable() {
+    let choice = |missing| {
+        interpreter("choice yes|$unset")
+            .with_missing_variables(missing)
+            .step()
+    };
+
+    assert_eq!(
+        choice(MissingVariable::Placeholder).unwrap(),
+        Event::Choice {
+            options: vec!["yes".to_owned(), "{$unset}".to_owned()]
+        }
+    );
+    assert_eq!(
+        choice(MissingVariable::Empty).unwrap(),
+        Event::Choice {
+            options: vec!["yes".to_owned(), String::new()]
+        }
+    );
+    assert_eq!(
+        choice(MissingVariable::Error).unwrap_err(),
+        InterpreterError::Render(RenderError::MissingVariable(
+            "unset".to_owned()
+        ))
+    );
+}
+
+#[test]
+fn test_interpreter_goto_and_random() {
+    let mut interpreter = interpreter(
+        "goto skip\ntext skipped\nlabel skip\nrandom r 4 4\nif r == \
+         4\ntext four\nfi",
+    );
+
+    assert_eq!(plain_text(interpreter.step().unwrap()), "four");
+    assert_eq!(interpreter.step().unwrap(), Event::Finished);
+}
+
+#[test]
+fn test_interpreter_jump() {
+    let mut interpreter = interpreter("jump next.scr start");
+
+    assert_eq!(
+        interpreter.step().unwrap(),
+        Event::Jump {
+            file: "next.scr".into(),
+            label: Some("start".to_owned())
+        }
+    );
+
+    let next = Script::new(
+        "text skipped\nlabel start\ntext next"
+            .parse_script()
+            .unwrap(),
+    );
+    interpreter
+        .load_script("next.scr", next, Some("start"))
+        .unwrap();
+    assert_eq!(plain_text(interpreter.step().unwrap()), "next");
+}
+
+#[test]
+fn test_interpreter_comparison() {
+    let mut interpreter = interpreter(
+        "setvar affection = 12\nif affection >= 10\ntext high\nfi\nif \
+         affection < 10\ntext low\nfi",
+    );
+
+    assert_eq!(plain_text(interpreter.step().unwrap()), "high");
+    assert_eq!(interpreter.step().unwrap(), Event::Finished);
+}
+
+#[test]
+fn test_novel_zipped_resources() {
+    let root = test_directory("zipped-resources");
+    write_novel(&root, &["background", "script"]);
+
+    let novel = Novel::try_load(&root).unwrap();
+    assert_eq!(novel.info.title, "Test novel");
+    assert_eq!(novel.device_resolution(), (256, 192));
+
+    let background = novel.background("bg.jpg").unwrap();
+    assert_eq!(background.read().unwrap(), b"background");
+    assert_eq!(background.filesystem_path(), None);
+    assert!(novel.background("missing.jpg").is_err());
+    assert_eq!(
+        novel
+            .foreground("fg.png")
+            .unwrap()
+            .filesystem_path(),
+        Some(root.join("foreground/fg.png"))
+    );
+
+    let mut script = novel.try_load_script("main.scr").unwrap();
+    assert!(matches!(
+        script.next_command(),
+        ScriptControlFlow::Execute(Command::BgLoad { .. })
+    ));
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_novel_single_archive() {
+    let root = test_directory("single-archive");
+    let sound = zip_bytes([("music.aac", b"music".as_slice())]);
+    let files = NOVEL_FILES
+        .iter()
+        .filter(|(name, _)| !name.starts_with("sound/"))
+        .map(|(name, contents)| {
+            (format!("novel/{name}"), contents.as_bytes())
+        })
+        .collect::<Vec<_>>();
+
+    let archive = root.join("novel.zip");
+    fs::write(
+        &archive,
+        zip_bytes(
+            files
+                .iter()
+                .map(|(name, contents)| (name.as_str(), *contents))
+                .chain([("novel/sound.zip", sound.as_slice())]),
+        ),
+    )
+    .unwrap();
+
+    let novel = Novel::try_load(&archive).unwrap();
+    assert_eq!(novel.info.title, "Test novel");
+    assert_eq!(
+        novel.sound("music.aac").unwrap().read().unwrap(),
+        b"music"
+    );
+    assert_eq!(novel.resources.icon.high.read().unwrap(), b"");
+    assert!(novel.try_load_script("main.scr").is_ok());
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_novel_in_memory() {
+    let novel = Novel::try_load_from(Arc::new(memory_novel())).unwrap();
+
+    assert_eq!(novel.info.title, "Test novel");
+    assert_eq!(
+        novel
+            .foreground("fg.png")
+            .unwrap()
+            .read()
+            .unwrap(),
+        b"foreground"
+    );
+    assert_eq!(
+        novel
+            .foreground("fg.png")
+            .unwrap()
+            .filesystem_path(),
+        None
+    );
+    assert_eq!(
+        novel.resources.script.files().unwrap(),
+        [PathBuf::from("main.scr")]
+    );
+}
+
+#[test]
+fn test_novel_overlay() {
+    let root = test_directory("overlay");
+    write_novel(&root, &["script"]);
+
+    let translation = MemorySource::new()
+        .with_file("script/main.scr", "text translated")
+        .with_file("script/extra.scr", "text extra");
+    let novel = Novel::try_load_from(Arc::new(OverlaySource::new(vec![
+        Arc::new(translation),
+        Arc::new(DirectorySource::new(&root)),
+    ])))
+    .unwrap();
+
+    assert!(matches!(
+        novel
+            .try_load_script("main.scr")
+            .unwrap()
+            .next_command(),
+        ScriptControlFlow::Execute(Command::Text(..))
+    ));
+    assert_eq!(
+        novel.resources.script.files().unwrap(),
+        [PathBuf::from("extra.scr"), PathBuf::from("main.scr")]
+    );
+    assert_eq!(
+        novel
+            .background("bg.jpg")
+            .unwrap()
+            .filesystem_path(),
+        Some(root.join("background/bg.jpg"))
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_directory_outside_root() {
+    let root = test_directory("outside-root");
+    fs::create_dir_all(root.join("novel/script")).unwrap();
+    fs::write(root.join("secret.txt"), "secret").unwrap();
+
+    let source = DirectorySource::new(root.join("novel"));
+    for path in [Path::new("../secret.txt"), &root.join("secret.txt")] {
+        assert!(!source.exists(path));
+        assert_eq!(source.filesystem_path(path), None);
+        assert_eq!(
+            source.read(path).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+    assert!(source.subdirectory("..").is_err());
+    assert!(source.subdirectory("script").unwrap().is_some());
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_zip_outside_root() {
+    let bytes = zip_bytes([
+        ("script/main.scr", b"text inside".as_slice()),
+        ("main.scr", b"text outside".as_slice()),
+    ]);
+    let archive =
+        Archive::from_reader("script.zip", Box::new(Cursor::new(bytes)))
+            .unwrap();
+    let source = ZipSource::named(archive, "script");
+
+    assert_eq!(
+        source.read(Path::new("./main.scr")).unwrap(),
+        b"text inside"
+    );
+    for path in ["../main.scr", "/main.scr", "nested/../main.scr"] {
+        let path = Path::new(path);
+        assert!(!source.exists(path));
+        assert_eq!(
+            source.read(path).unwrap_err().kind(),
+            io::ErrorKind::InvalidInput
+        );
+    }
+    assert!(source.subdirectory("..").is_err());
+}
+
+#[test]
+fn test_index() {
+    let scripts = MemorySource::new()
+        .with_file(
+            "main.scr",
+            "bgload bg.jpg\nsetvar route = 1\nchoice a|$b\nif route == \
+             target\njump other.scr end\nfi\nbogus command",
+        )
+        .with_file("other.scr", "label end\ntext {$name}\nmusic bgm.aac")
+        .with_file("readme.txt", "not a script");
+    let index = NovelIndex::build(&scripts).unwrap();
+
+    assert_eq!(index.scripts().count(), 2);
+    assert!(index.script("readme.txt").is_none());
+
+    let main = index.script("main.scr").unwrap();
+    assert_eq!(
+        main.variables.iter().collect::<Vec<_>>(),
+        ["b", "route", "selected", "target"]
+    );
+    assert_eq!(main.jumps[0].label.as_deref(), Some("end"));
+    assert_eq!(main.jumps[0].span.line, 5);
+    assert_eq!(main.diagnostics.len(), 1);
+    assert_eq!(main.commands.len(), 7);
+
+    assert_eq!(
+        index
+            .label_definitions("end")
+            .map(|(script, index)| (script.path.clone(), index))
+            .collect::<Vec<_>>(),
+        [(PathBuf::from("other.scr"), 0)]
+    );
+    assert_eq!(index.jumps_to(Path::new("other.scr")).count(), 1);
+    assert_eq!(index.variable_users("name").count(), 1);
+    assert_eq!(
+        index
+            .resource_users(ResourceKind::Music, Path::new("bgm.aac"))
+            .count(),
+        1
+    );
+}
+
+#[test]
+fn test_info() {
+    let info: NovelInfo = "title=Test novel\n# comment\nauthor = \
+                           someone\nlanguage=en"
+        .parse()
+        .unwrap();
+
+    assert_eq!(info.title, "Test novel");
+    assert_eq!(info.author.as_deref(), Some("someone"));
+    assert_eq!(info.version, None);
+    assert_eq!(info.extra("language"), Some("en"));
+}
+
+#[test]
+fn test_info_encodings() {
+    let utf8 = b"\xEF\xBB\xBFtitle=Caf\xC3\xA9";
+    let latin1 = b"title=Caf\xE9";
+    let utf16: Vec<u8> = [0xFF, 0xFE]
+        .into_iter()
+        .chain(
+            "title=Café"
+                .encode_utf16()
+                .flat_map(u16::to_le_bytes),
+        )
+        .collect();
+
+    for bytes in [utf8.as_slice(), latin1, &utf16] {
+        assert_eq!(NovelInfo::from_bytes(bytes).unwrap().title, "Café");
+    }
+}
+
+#[test]
+fn test_info_errors() {
+    assert_eq!(
+        "author=someone".parse::<NovelInfo>().unwrap_err(),
+        InfoParseError::NoTitle
+    );
+
+    let info = "title=a\nbroken line\ntitle=b\nkey=1\nkey=2"
+        .parse::<NovelInfo>()
+        .unwrap();
+    assert_eq!(info.title, "b");
+    assert_eq!(info.extra("key"), Some("2"));
+    assert_eq!(
+        info.warnings,
+        [
+            InfoParseError::MalformedLine {
+                line: 2,
+                text: "broken line".to_owned()
+            },
+            InfoParseError::DuplicateKey {
+                line: 3,
+                key: "title".to_owned()
+            },
+            InfoParseError::DuplicateKey {
+                line: 5,
+                key: "key".to_owned()
+            },
+        ]
+    );
+}
+
+#[test]
+fn test_img_ini() {
+    let source = [
+        "height=192",
+        "width=256",
+        "fontsize=12",
+        "textbox_x=4",
+        "textbox_y=120",
+        "textbox_width=248",
+        "textbox_height=68",
+        "skin=dark",
+    ]
+    .join("\n");
+    let mut img: ImgIni = source.parse().unwrap();
+
+    assert_eq!(img.resolution(), (256, 192));
+    assert_eq!(img.font_size, Some(12));
+    assert_eq!(
+        img.text_box,
+        Some(TextBox {
+            x: 4,
+            y: 120,
+            width: 248,
+            height: 68
+        })
+    );
+    assert_eq!(img.text_color, None);
+    assert_eq!(img.get::<String>("skin"), Some(Ok("dark".to_owned())));
+    assert_eq!(img.get::<u16>("missing"), None);
+
+    img.width = 512;
+    img.text_box = None;
+    img.text_color = Some(Rgb {
+        r: 0xff,
+        g: 0xee,
+        b: 0x00,
+    });
+    img.set("skin", "light");
+    img.set("frame", 2);
+    assert_eq!(
+        img.to_string().lines().collect::<Vec<_>>(),
+        [
+            "width=512",
+            "height=192",
+            "fontsize=12",
+            "textcolor=ffee00",
+            "skin=light",
+            "frame=2"
+        ]
+    );
+    assert_eq!(img.to_string().parse::<ImgIni>().unwrap(), img);
+}
+
+#[test]
+fn test_img_ini_errors() {
+    assert_eq!(
+        "width=256".parse::<ImgIni>().unwrap_err(),
+        ImgIniError::MissingKey("height")
+    );
+    assert_eq!(
+        "width=256\nheight=big"
+            .parse::<ImgIni>()
+            .unwrap_err(),
+        ImgIniError::InvalidValue {
+            key: "height".to_owned(),
+            value: "big".to_owned()
+        }
+    );
+    assert_eq!(
+        "width=256\nheight=192\ntextbox_x=4"
+            .parse::<ImgIni>()
+            .unwrap_err(),
+        ImgIniError::MissingKey("textbox_y")
+    );
+    assert_eq!(
+        "width=256\nheight=192\ntextcolor=white"
+            .parse::<ImgIni>()
+            .unwrap_err(),
+        ImgIniError::InvalidValue {
+            key: "textcolor".to_owned(),
+            value: "white".to_owned()
+        }
+    );
+    assert_eq!(
+        "width=256\nheight=192\nframe=left"
+            .parse::<ImgIni>()
+            .unwrap()
+            .get::<u16>("frame"),
+        Some(Err(ImgIniError::InvalidValue {
+            key: "frame".to_owned(),
+            value: "left".to_owned()
+        }))
+    );
+}
+
+#[test]
+fn test_validate() {
+    let source = memory_novel()
+        .with_file(
+            "script/main.scr",
+            "bgload missing.jpg\nsetimg fg.png 0 0\ngoto nowhere\nif a \
+             == 1\nfi\nfi\nif b == 2\njump other.scr\njump broken.scr \
+             start\njump main.scr nowhere",
+        )
+        .with_file("script/broken.scr", "label end\nsetimg");
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+
+    let findings = validate::validate(&novel)
+        .into_iter()
+        .filter(|f| f.severity == Severity::Error)
+        .map(|f| (f.file, f.span.line, f.problem))
+        .collect::<Vec<_>>();
+    let lines = findings
+        .iter()
+        .map(|(script, line, problem)| {
+            format!("{}:{line}: {problem}", script.display())
+        })
+        .collect::<Vec<_>>();
+
+    assert!(matches!(findings[0].2, Problem::Parse(..)));
+    assert_eq!(
+        lines[1..],
+        [
+            "script/main.scr:1: background missing.jpg was not found",
+            "script/main.scr:3: label nowhere is not defined",
+            "script/main.scr:6: fi without matching if",
+            "script/main.scr:7: if without matching fi",
+            "script/main.scr:8: script other.scr was not found",
+            "script/main.scr:9: label start is not defined in broken.scr",
+            "script/main.scr:10: label nowhere is not defined in main.scr",
+        ]
+    );
+}
+
+#[test]
+fn test_unreadable_script() {
+    // "konnichiwa" in Shift-JIS
+    let source = memory_novel()
+        .with_file("script/main.scr", "jump sjis.scr")
+        .with_file(
+            "script/sjis.scr",
+            b"text \x82\xb1\x82\xf1\x82\xc9\x82\xbf\x82\xcd".as_slice(),
+        );
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+
+    let script = novel.index.script("sjis.scr").unwrap();
+    assert!(script.commands.is_empty());
+    assert_eq!(
+        script.read_error.as_ref().unwrap().kind(),
+        io::ErrorKind::InvalidData
+    );
+
+    let findings = validate::validate(&novel)
+        .into_iter()
+        .filter(|f| f.severity == Severity::Error)
+        .map(|f| f.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(findings.len(), 1);
+    assert!(findings[0].starts_with(
+        "script/sjis.scr:1:1: error: script can't be read: invalid utf-8"
+    ));
+}
+
+#[test]
+fn test_validate_info() {
+    let source = memory_novel()
+        .with_file("info.txt", "title=a\nbroken line\ntitle=b")
+        .with_file("script/main.scr", "text hello");
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+    assert_eq!(novel.info.title, "b");
+
+    let findings = validate::validate(&novel)
+        .into_iter()
+        .map(|f| f.to_string())
+        .collect::<Vec<_>>();
+    assert_eq!(
+        findings,
+        [
+            "info.txt:2:1: warning: expected key=value, found broken \
+             line, line is skipped",
+            "info.txt:3:1: warning: key title is specified twice, the \
+             last value is used",
+        ]
+    );
+}
+
+#[test]
+fn test_lint() {
+    let source = memory_novel()
+        .with_file(
+            "script/main.scr",
+            "choice a|b\nif selected == 1\ngoto end\nfi\nif flag == \
+             1\nfi\njump next.scr\ntext dead\nlabel end\nlabel \
+             unused\ntext bye",
+        )
+        .with_file("script/next.scr", "text next")
+        .with_file("script/orphan.scr", "text lonely");
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+
+    let findings = validate::validate(&novel);
+    assert!(findings
+        .iter()
+        .all(|f| f.severity == Severity::Warning));
+    assert_eq!(
+        findings
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        [
+            "script/main.scr:5:1: warning: variable flag is never set",
+            "script/main.scr:8:1: warning: code is never executed",
+            "script/main.scr:10:1: warning: label unused is never used",
+            "script/orphan.scr:1:1: warning: script is not reachable \
+             from main.scr",
+        ]
+    );
+}
+
+#[test]
+fn test_save_roundtrip() {
+    let source = memory_novel()
+        .with_file("script/main.scr", "jump other.scr")
+        .with_file(
+            "script/other.scr",
+            "bgload old.jpg\nsetimg old.png 0 0\nbgload bg.jpg\nsetimg \
+             fg.png 10 20\nmusic music.aac\nsetvar a = 3\ntext \
+             Hello\ncleartext\ntext !\nchoice x|y\ntext after $selected",
+        );
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+
+    let mut interpreter =
+        Interpreter::new(novel.try_load_script("main.scr").unwrap())
+            .with_random(Sequence::new([1]));
+    assert!(matches!(interpreter.step().unwrap(), Event::Jump { .. }));
+    interpreter
+        .load_script(
+            "other.scr",
+            novel.try_load_script("other.scr").unwrap(),
+            None,
+        )
+        .unwrap();
+    while !matches!(interpreter.step().unwrap(), Event::Choice { .. }) {}
+
+    let save = SaveState::capture(&interpreter);
+    let written = save.to_string();
+    assert_eq!(
+        written.lines().collect::<Vec<_>>(),
+        [
+            "vrs-save 2",
+            "file other.scr",
+            "cursor 9",
+            "background bg.jpg",
+            "foreground 10 20 fg.png",
+            "music music.aac",
+            "random 0",
+            "var a 3",
+            "text Hello",
+            "text !",
+        ]
+    );
+    assert_eq!(written.parse::<SaveState>().unwrap(), save);
+
+    let mut restored = save
+        .restore(&novel, Variables::default())
+        .unwrap();
+    assert_eq!(restored.scene(), interpreter.scene());
+    assert_eq!(restored.variables().get("a"), 3);
+    assert_eq!(
+        restored.step().unwrap(),
+        Event::Choice {
+            options: vec!["x".to_owned(), "y".to_owned()]
+        }
+    );
+    restored.choose(1).unwrap();
+    assert_eq!(plain_text(restored.step().unwrap()), "after selected");
+}
+
+#[test]
+fn test_save_after_finished() {
+    let source = memory_novel().with_file("script/main.scr", "text only");
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+
+    let mut interpreter =
+        Interpreter::new(novel.try_load_script("main.scr").unwrap());
+    interpreter.step().unwrap();
+    assert_eq!(interpreter.step().unwrap(), Event::Finished);
+    assert_eq!(interpreter.step().unwrap(), Event::Finished);
+
+    let save = SaveState::capture(&interpreter);
+    assert_eq!(save.cursor, 1);
+
+    let mut restored = save
+        .restore(&novel, Variables::default())
+        .unwrap();
+    assert_eq!(restored.step().unwrap(), Event::Finished);
+}
+
+#[test]
+fn test_save_errors() {
+    let parse = |s: &str| s.parse::<SaveState>().unwrap_err();
+
+    assert!(matches!(parse("file main.scr"), SaveParseError::NoHeader));
+    assert!(matches!(
+        parse("vrs-save 99\nfile main.scr\ncursor 0"),
+        SaveParseError::UnsupportedVersion(v) if v == "99"
+    ));
+    assert!(matches!(
+        parse("vrs-save 1\ncursor 0"),
+        SaveParseError::MissingKey("file")
+    ));
+    assert!(matches!(
+        parse("vrs-save 1\nfile main.scr\ncursor x"),
+        SaveParseError::MalformedLine { line: 3, .. }
+    ));
+    assert!(matches!(
+        parse("vrs-save 1\nforeground 1 fg.png"),
+        SaveParseError::MalformedLine { line: 2, .. }
+    ));
+
+    let novel = Novel::try_load_from(Arc::new(memory_novel())).unwrap();
+    let save: SaveState = "vrs-save 1\nfile main.scr\ncursor 3"
+        .parse()
+        .unwrap();
+    assert!(matches!(
+        save.restore(&novel, Variables::default()),
+        Err(RestoreSaveError::CursorOutOfRange { cursor: 3, len: 2 })
+    ));
+}
+
+#[test]
+fn test_global_store() {
+    let directory = test_directory("global-store").join("save");
+    let mut store = GlobalStore::open(&directory).unwrap();
+    assert!(store.variables().is_empty());
+
+    let mut variables = Variables::default();
+    variables.set("unlocked", 1, VariableStorageType::Global);
+    variables.set("local", 2, VariableStorageType::Local);
+    assert!(store.update(&variables).unwrap());
+    assert!(!store.update(&variables).unwrap());
+
+    assert_eq!(
+        fs::read_to_string(directory.join(global::GLOBAL_FILE)).unwrap(),
+        "vrs-global 1\nunlocked 1\n"
+    );
+    assert_eq!(fs::read_dir(&directory).unwrap().count(), 1);
+
+    let mut loaded = Variables::default();
+    GlobalStore::open(&directory)
+        .unwrap()
+        .load_into(&mut loaded);
+    assert_eq!(loaded.get("unlocked"), 1);
+    assert_eq!(loaded.get("local"), 0);
+
+    fs::write(directory.join(global::GLOBAL_FILE), "vrs-global 2")
+        .unwrap();
+    assert!(matches!(
+        GlobalStore::open(&directory),
+        Err(GlobalStoreError::UnsupportedVersion(v)) if v == "2"
+    ));
+    fs::write(directory.join(global::GLOBAL_FILE), "vrs-global 1\nx")
+        .unwrap();
+    assert!(matches!(
+        GlobalStore::open(&directory),
+        Err(GlobalStoreError::MalformedLine { line: 2, .. })
+    ));
+
+    let root = directory.parent().unwrap();
+    assert_eq!(global::save_directory(root), directory);
+    assert_eq!(
+        global::save_directory(root.join("novel.zip")),
+        root.join("novel-save")
+    );
+
+    fs::remove_dir_all(root).unwrap();
+}
+
+#[test]
+fn test_vnds_save() {
+    // the fixture is synthetic, written by hand after the
+    // save format of VNDS rather than saved by the engine
+    let save = VndsSave::from_bytes(include_bytes!(
+        "../fixtures/vnds/save00.sav"
+    ))
+    .unwrap();
+    assert_eq!(
+        save,
+        VndsSave {
+            file: "chapter1.scr".into(),
+            position: 4,
+            date: Some("2010/05/01 12:34".to_owned()),
+            variables: [
+                ("met_&_talked".to_owned(), 1),
+                ("route".to_owned(), 2)
+            ]
+            .into(),
+            skipped: vec!["nickname".to_owned()],
+            music: Some("theme.aac".into()),
+            background: Some("room.jpg".into()),
+            sprites: vec![("girl.png".into(), (40, 8))],
+        }
+    );
+    assert_eq!(
+        save.to_string().parse::<VndsSave>().unwrap(),
+        VndsSave {
+            skipped: Vec::new(),
+            ..save.clone()
+        }
+    );
+
+    let source = memory_novel().with_file(
+        "script/chapter1.scr",
+        "bgload room.jpg\n\ntext one\ntext two\ntext three",
+    );
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+
+    let state = save.to_save_state(&novel).unwrap();
+    assert_eq!(state.cursor, 2);
+    assert_eq!(state.scene.foreground, save.sprites);
+
+    let mut interpreter = state
+        .restore(&novel, Variables::default())
+        .unwrap();
+    assert_eq!(interpreter.variables().get("route"), 2);
+    assert_eq!(plain_text(interpreter.step().unwrap()), "two");
+
+    let converted = VndsSave::from_save_state(&state, &novel).unwrap();
+    assert_eq!(converted.position, 4);
+    assert_eq!(converted.variables, save.variables);
+}
+
+#[test]
+fn test_vnds_global() {
+    let global =
+        vnds::read_global(include_bytes!("../fixtures/vnds/global.sav"))
+            .unwrap();
+    assert_eq!(
+        global,
+        VndsGlobal {
+            variables: [
+                ("clears".to_owned(), 3),
+                ("ending_a".to_owned(), 1)
+            ]
+            .into(),
+            skipped: vec!["player".to_owned()],
+        }
+    );
+    assert_eq!(
+        vnds::read_global(
+            vnds::write_global(&global.variables).as_bytes()
+        )
+        .unwrap(),
+        VndsGlobal {
+            skipped: Vec::new(),
+            ..global
+        }
+    );
+
+    assert!(matches!(
+        vnds::read_global(b"<save></save>"),
+        Err(VndsSaveError::UnexpectedRoot {
+            expected: "global",
+            ..
+        })
+    ));
+    assert!(matches!(
+        vnds::read_global(b"<global><var></global>"),
+        Err(VndsSaveError::Xml { offset: 13 })
+    ));
+}
+
+#[test]
+fn test_random_sequence() {
+    let mut interpreter = interpreter(
+        "random a 1 10\nrandom b 1 10\nrandom c 1 10\ntext $a $b $c",
+    )
+    .with_random(Sequence::new([5, 9, 200]));
+    interpreter.step().unwrap();
+
+    let variables = interpreter.variables();
+    assert_eq!(
+        ["a", "b", "c"].map(|name| variables.get(name)),
+        [5, 9, 10]
+    );
+}
+
+#[test]
+fn test_random_seeded() {
+    let mut a = XorShift::seeded(42);
+    let mut b = XorShift::seeded(42);
+    let draw = |rng: &mut XorShift| {
+        (0..16)
+            .map(|_| rng.next_in(1..=1000))
+            .collect::<Vec<_>>()
+    };
+
+    assert_eq!(draw(&mut a), draw(&mut b));
+    assert_ne!(draw(&mut a), draw(&mut XorShift::seeded(43)));
+    assert!(draw(&mut a)
+        .into_iter()
+        .all(|value| (1..=1000).contains(&value)));
+}
+
+#[test]
+fn test_random_survives_save() {
+    let source = memory_novel().with_file(
+        "script/main.scr",
+        "random a 1 60000\ntext first\nrandom b 1 60000\ntext second",
+    );
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+
+    let mut interpreter =
+        Interpreter::new(novel.try_load_script("main.scr").unwrap())
+            .with_random(XorShift::seeded(7));
+    interpreter.step().unwrap();
+    let save: SaveState = SaveState::capture(&interpreter)
+        .to_string()
+        .parse()
+        .unwrap();
+    interpreter.step().unwrap();
+
+    let mut restored = save
+        .restore_with(&novel, Variables::default(), XorShift::seeded(8))
+        .unwrap();
+    restored.step().unwrap();
+    assert_eq!(
+        restored.variables().get("b"),
+        interpreter.variables().get("b")
+    );
+
+    let legacy: SaveState = "vrs-save 1\nfile main.scr\ncursor 2"
+        .parse()
+        .unwrap();
+    assert_eq!(legacy.random, None);
+}
+
+#[test]
+fn test_runtime_jumps() {
+    let source = memory_novel()
+        .with_file(
+            "script/main.scr",
+            "text a\njump other.scr middle\nlabel end\ntext c",
+        )
+        .with_file(
+            "script/other.scr",
+            "text skipped\nlabel middle\ntext b\njump main.scr end",
+        );
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+    let mut runtime = Runtime::new(&novel).unwrap();
+
+    let texts = (0..3)
+        .map(|_| plain_text(runtime.step().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(texts, ["a", "b", "c"]);
+    assert_eq!(runtime.interpreter().file(), Path::new("main.scr"));
+    assert_eq!(runtime.step().unwrap(), Event::Finished);
+}
+
+#[test]
+fn test_runtime_jump_errors() {
+    let source = memory_novel()
+        .with_file("script/main.scr", "text a\n  jump missing.scr")
+        .with_file("script/label.scr", "jump main.scr nowhere");
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+
+    let mut runtime = Runtime::new(&novel).unwrap();
+    runtime.step().unwrap();
+    match runtime.step().unwrap_err() {
+        RuntimeError::LoadScript {
+            call_site, file, ..
+        } => {
+            assert_eq!(call_site.to_string(), "script/main.scr:2:3");
+            assert_eq!(file, Path::new("missing.scr"));
+        }
+        e => panic!("Expected script load error, got {e:?}"),
+    }
+
+    let mut runtime = Runtime::new(&novel).unwrap();
+    runtime.jump("label.scr".into(), None).unwrap();
+    assert_eq!(
+        runtime.step().unwrap_err().to_string(),
+        "script/label.scr:1:1: label nowhere is not defined in main.scr"
+    );
+}
+
+#[test]
+fn test_control_flow_graph() {
+    let source = memory_novel()
+        .with_file(
+            "script/main.scr",
+            "text start\nchoice a|b\nif selected == 1\ngoto \
+             left\nfi\njump other.scr\nlabel left\ntext left",
+        )
+        .with_file(
+            "script/other.scr",
+            "text other\njump missing.scr\nlabel orphan\ntext never",
+        );
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+    let graph = ControlFlowGraph::build(&novel.index);
+
+    let blocks = graph
+        .blocks()
+        .iter()
+        .map(|block| {
+            let edges = block
+                .edges
+                .iter()
+                .map(|edge| (edge.kind.clone(), edge.target.clone()))
+                .collect::<Vec<_>>();
+            (block.commands.clone(), edges)
+        })
+        .collect::<Vec<_>>();
+    let branch = |taken| EdgeKind::Branch {
+        condition: "selected == 1".to_owned(),
+        taken,
+    };
+    assert_eq!(
+        blocks,
+        [
+            (
+                0..2,
+                vec![(
+                    EdgeKind::Choice {
+                        options: vec!["a".to_owned(), "b".to_owned()]
+                    },
+                    Target::Block(1)
+                )]
+            ),
+            (
+                2..3,
+                vec![
+                    (branch(true), Target::Block(2)),
+                    (branch(false), Target::Block(3))
+                ]
+            ),
+            (3..4, vec![(EdgeKind::Goto, Target::Block(4))]),
+            (4..6, vec![(EdgeKind::Jump, Target::Block(5))]),
+            (6..8, vec![]),
+            (
+                0..2,
+                vec![(
+                    EdgeKind::Jump,
+                    Target::MissingScript("missing.scr".into())
+                )]
+            ),
+            (2..4, vec![]),
+        ]
+    );
+
+    assert_eq!(
+        graph.unreachable_labels().collect::<Vec<_>>(),
+        [(Path::new("other.scr"), "orphan")]
+    );
+    assert_eq!(
+        graph
+            .dead_ends()
+            .map(|block| block.span.unwrap().line)
+            .collect::<Vec<_>>(),
+        [7, 1]
+    );
+
+    let dot = graph.to_dot();
+    assert!(dot.starts_with("digraph novel {"));
+    assert!(dot.contains("b1 -> b2 [label=\"selected == 1\"];"));
+    assert!(
+        dot.contains("b4 [label=\"main.scr:7\\nlabel left\", color=red")
+    );
+    assert!(dot.contains("b5 -> m0 [style=dashed];"));
+
+    let mermaid = graph.to_mermaid();
+    assert!(mermaid.contains("b1 -.->|\"else\"| b3"));
+    assert!(mermaid.contains("class b6 unreachable"));
+    assert!(mermaid.contains("m0{{\"missing script missing.scr\"}}"));
+}
+
+#[test]
+fn test_variable_analysis() {
+    let source = memory_novel().with_file(
+        "script/main.scr",
+        "setvar route = 0\ngsetvar seen + 1\nchoice a|b\nif selected == \
+         1\nsetvar flag = 1\nfi\nif flag == 1\ntext got $flag\nfi\nif \
+         rout == 0\nfi\nif seen > 1\nfi",
+    );
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+    let analysis = VariableAnalysis::analyze(&novel.index);
+
+    let lines = |accesses: &mut dyn Iterator<Item = &Access>| {
+        accesses
+            .map(|access| access.span.line)
+            .collect::<Vec<_>>()
+    };
+    let usage = analysis
+        .variables
+        .iter()
+        .map(|(name, usage)| {
+            (
+                name.as_str(),
+                usage.storage,
+                lines(&mut usage.writes()),
+                lines(&mut usage.reads()),
+                lines(&mut usage.read_before_write.iter()),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        usage,
+        [
+            (
+                "flag",
+                VariableStorage::Local,
+                vec![5],
+                vec![7, 8],
+                vec![7, 8]
+            ),
+            ("rout", VariableStorage::Unknown, vec![], vec![10], vec![10]),
+            ("route", VariableStorage::Local, vec![1], vec![], vec![]),
+            ("seen", VariableStorage::Global, vec![2], vec![12], vec![]),
+            ("selected", VariableStorage::Local, vec![3], vec![4], vec![]),
+        ]
+    );
+    assert_eq!(
+        analysis.typo_suspects,
+        [("rout".to_owned(), "route".to_owned())]
+    );
+}
+
+#[test]
+fn test_playthrough() {
+    let source = memory_novel()
+        .with_file(
+            "script/main.scr",
+            "bgload bg.jpg\nchoice left|right\nif selected == 2\njump \
+             right.scr\nfi\ntext went $selected",
+        )
+        .with_file("script/right.scr", "random r 5 5\ntext rolled $r");
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+
+    let transcript = |policy| {
+        let mut playthrough = Playthrough::new(&novel, policy).unwrap();
+        playthrough.run().unwrap();
+        playthrough.into_transcript().to_string()
+    };
+    assert_eq!(
+        transcript(ChoicePolicy::First),
+        "script/main.scr:1:1: bgload bg.jpg 16\nscript/main.scr:2:1: \
+         choice left|right -> 1\nscript/main.scr:6:1: text went \
+         1\nscript/main.scr: end\n"
+    );
+    assert_eq!(
+        transcript(ChoicePolicy::Scripted(vec![1])),
+        transcript(ChoicePolicy::Last)
+    );
+    assert!(transcript(ChoicePolicy::Last).ends_with(
+        "script/right.scr:2:1: text rolled 5\nscript/right.scr: end\n"
+    ));
+
+    let mut playthrough =
+        Playthrough::new(&novel, ChoicePolicy::Scripted(vec![])).unwrap();
+    assert!(matches!(
+        playthrough.run(),
+        Err(PlaythroughError::ChoicesExhausted { number: 0, .. })
+    ));
+    assert_eq!(playthrough.transcript().entries.len(), 1);
+
+    let mut playthrough =
+        Playthrough::new(&novel, ChoicePolicy::Scripted(vec![2])).unwrap();
+    assert_eq!(
+        playthrough.run().unwrap_err().to_string(),
+        "script/main.scr:2:1: option 3 is out of range, choice has 2 \
+         options"
+    );
+
+    let source =
+        memory_novel().with_file("script/main.scr", "text $unset");
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+    let mut playthrough =
+        Playthrough::new(&novel, ChoicePolicy::First).unwrap();
+    playthrough.run().unwrap();
+    assert!(playthrough
+        .transcript()
+        .to_string()
+        .starts_with("script/main.scr:1:1: text \\{\\$unset}\n"));
+
+    let mut playthrough =
+        Playthrough::new(&novel, ChoicePolicy::First).unwrap();
+    playthrough.missing_variables = MissingVariable::Error;
+    assert_eq!(
+        playthrough.run().unwrap_err().to_string(),
+        "script/main.scr:1:1: Variable unset is not set"
+    );
+
+    let source = memory_novel().with_file(
+        "script/main.scr",
+        "choice a|$unset
+text done",
+    );
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+    let mut playthrough =
+        Playthrough::new(&novel, ChoicePolicy::First).unwrap();
+    playthrough.missing_variables = MissingVariable::Error;
+    assert!(matches!(
+        playthrough.run().unwrap_err(),
+        PlaythroughError::Runtime(RuntimeError::Interpreter(
+            InterpreterError::Render(RenderError::MissingVariable(name))
+        )) if name == "unset"
+    ));
+}
+
+#[test]
+fn test_route_explorer() {
+    let source = memory_novel()
+        .with_file(
+            "script/main.scr",
+            "label top\nchoice good|bad|loop\nif selected == 1\njump \
+             good.scr\nfi\nif selected == 3\ngoto top\nfi\nsetvar flag = \
+             1\nif flag == 0\nchoice never|ever\nfi\nlabel bad\ntext bad",
+        )
+        .with_file("script/good.scr", "text good\nlabel ending\ntext yes");
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+
+    let exploration = Explorer::new(&novel).explore().unwrap();
+    assert!(!exploration.truncated());
+    assert!(exploration.failures.is_empty());
+    assert_eq!(exploration.states, 2);
+
+    let endings = exploration
+        .endings
+        .iter()
+        .map(|ending| {
+            let paths = ending
+                .paths
+                .iter()
+                .map(|path| {
+                    path.iter()
+                        .map(|step| step.option + 1)
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>();
+            (
+                ending.file.to_str().unwrap(),
+                ending.label.as_deref(),
+                paths,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        endings,
+        [
+            ("good.scr", Some("ending"), vec![vec![1], vec![3, 1]]),
+            ("main.scr", Some("bad"), vec![vec![2], vec![3, 2]]),
+        ]
+    );
+    assert_eq!(
+        exploration
+            .unreached
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>(),
+        [
+            "script/main.scr:11:1: 1) never",
+            "script/main.scr:11:1: 2) ever"
+        ]
+    );
+
+    let mut explorer = Explorer::new(&novel);
+    explorer.max_states = 1;
+    let exploration = explorer.explore().unwrap();
+    assert!(exploration.state_limit_reached);
+    assert!(!exploration.event_limit_reached);
+    assert_eq!(exploration.endings.len(), 2);
+}
+
+#[test]
+fn test_route_explorer_random() {
+    let source = memory_novel().with_file(
+        "script/main.scr",
+        "random a 1 3\nrandom b 0 1\nif a == 3\nif b == 1\nchoice \
+         lucky|unlucky\nfi\nfi\ntext end",
+    );
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+
+    let exploration = Explorer::new(&novel).explore().unwrap();
+    assert!(!exploration.truncated());
+    assert!(exploration.unreached.is_empty());
+    assert_eq!(exploration.endings.len(), 1);
+    assert_eq!(exploration.endings[0].paths.len(), 7);
+}
+
+#[test]
+fn test_route_explorer_event_limit() {
+    let source = memory_novel().with_file(
+        "script/main.scr",
+        "choice stay|leave
+if selected == 1
+label loop
+text again
+goto loop
+fi
+text bye",
+    );
+    let novel = Novel::try_load_from(Arc::new(source)).unwrap();
+
+    let mut explorer = Explorer::new(&novel);
+    explorer.max_events = 10;
+    let exploration = explorer.explore().unwrap();
+    assert!(exploration.event_limit_reached);
+    assert!(!exploration.state_limit_reached);
+    assert_eq!(exploration.endings.len(), 1);
+    assert_eq!(
+        exploration.endings[0]
+            .paths
+            .iter()
+            .map(|path| path
+                .iter()
+                .map(|step| &step.text)
+                .collect::<Vec<_>>())
+            .collect::<Vec<_>>(),
+        [["leave"]]
+    );
+}
@@ -0,0 +1,405 @@
+use {
+    crate::{
+        error::InfoParseError,
+        graph::{
+            ControlFlowGraph,
+            Target,
+        },
+        index::{
+            NovelIndex,
+            ResourceKind,
+            ScriptIndex,
+        },
+        interpreter::MAIN_SCRIPT,
+        novel::Novel,
+        variables::{
+            self,
+            AccessKind,
+        },
+    },
+    nds_parser::{
+        command::{
+            Command,
+            IfRhs,
+        },
+        error::{
+            LocatedParseError,
+            Severity,
+        },
+        span::Span,
+    },
+    std::{
+        collections::BTreeSet,
+        fmt,
+        io,
+        path::{
+            Path,
+            PathBuf,
+        },
+        sync::Arc,
+    },
+};
+
+#[derive(Debug, Clone)]
+pub enum Problem {
+    /// Script file could not be read
+    Unreadable(Arc<io::Error>),
+
+    /// Command failed to parse
+    Parse(LocatedParseError),
+
+    MissingResource {
+        kind: ResourceKind,
+        path: PathBuf,
+    },
+
+    /// `goto` to the label which is not defined in the
+    /// script
+    UndefinedLabel(String),
+
+    /// `jump` to the script which does not exist
+    MissingScript(PathBuf),
+
+    /// `jump` to the label which is not defined in the
+    /// target script
+    MissingJumpLabel {
+        file: PathBuf,
+        label: String,
+    },
+
+    /// `fi` without the matching `if`
+    UnmatchedEndIf,
+
+    /// `if` without the matching `fi`
+    UnterminatedIf,
+
+    /// Label is not a target of any `goto` or `jump`
+    UnusedLabel(String),
+
+    /// Commands after `goto` or `jump` which can never run
+    UnreachableCode,
+
+    /// Script can not be reached from the entry script
+    UnreachableScript,
+
+    /// `if` checks the variable which is never set
+    UnsetVariable(String),
+
+    /// Line of `info.txt` which was skipped or overridden
+    Info(InfoParseError),
+}
+
+/// Problem found in the novel
+#[derive(Debug, Clone)]
+pub struct Finding {
+    /// Path relative to the novel root
+    pub file: PathBuf,
+    pub span: Span,
+
+    pub severity: Severity,
+    pub problem: Problem,
+}
+
+/// Check every script of the novel. Findings are sorted by
+/// their location
+pub fn validate(novel: &Novel) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    for warning in &novel.info.warnings {
+        let line = match warning {
+            InfoParseError::MalformedLine { line, .. }
+            | InfoParseError::DuplicateKey { line, .. } => *line,
+            InfoParseError::NoTitle => 1,
+        };
+
+        findings.push(Finding {
+            file: PathBuf::from("info.txt"),
+            span: Span {
+                line,
+                column: 1,
+                length: 0,
+            },
+            severity: Severity::Warning,
+            problem: Problem::Info(warning.clone()),
+        });
+    }
+
+    for script in novel.index.scripts() {
+        validate_script(novel, script, &mut findings);
+    }
+
+    lint(&novel.index, &mut findings);
+
+    findings.sort_by(|a, b| (&a.file, a.span).cmp(&(&b.file, b.span)));
+    findings
+}
+
+fn validate_script(
+    novel: &Novel,
+    script: &ScriptIndex,
+    findings: &mut Vec<Finding>,
+) {
+    let mut report = |span, severity, problem| {
+        findings.push(Finding {
+            file: Path::new("script").join(&script.path),
+            span,
+            severity,
+            problem,
+        })
+    };
+
+    if let Some(error) = &script.read_error {
+        report(
+            Span {
+                line: 1,
+                column: 1,
+                length: 0,
+            },
+            Severity::Error,
+            Problem::Unreadable(error.clone()),
+        );
+    }
+
+    for diagnostic in &script.diagnostics {
+        report(
+            diagnostic.error.span,
+            diagnostic.severity,
+            Problem::Parse(diagnostic.error.clone()),
+        );
+    }
+
+    for resource in &script.resources {
+        let source = match resource.kind {
+            ResourceKind::Background => &novel.resources.background,
+            ResourceKind::Foreground => &novel.resources.foreground,
+            ResourceKind::Sound | ResourceKind::Music => {
+                &novel.resources.sound
+            }
+        };
+
+        if !source.exists(&resource.path) {
+            report(
+                resource.span,
+                Severity::Error,
+                Problem::MissingResource {
+                    kind: resource.kind,
+                    path: resource.path.clone(),
+                },
+            );
+        }
+    }
+
+    for jump in &script.jumps {
+        let problem = match (novel.index.script(&jump.file), &jump.label) {
+            (None, _) => Problem::MissingScript(jump.file.clone()),
+            (Some(target), Some(label))
+                if !target.labels.contains_key(label) =>
+            {
+                Problem::MissingJumpLabel {
+                    file: jump.file.clone(),
+                    label: label.clone(),
+                }
+            }
+
+            _ => continue,
+        };
+
+        report(jump.span, Severity::Error, problem);
+    }
+
+    let mut open_ifs = Vec::new();
+    for command in &script.commands {
+        match &command.inner {
+            Command::Goto(label) if !script.labels.contains_key(label) => {
+                report(
+                    command.span,
+                    Severity::Error,
+                    Problem::UndefinedLabel(label.clone()),
+                )
+            }
+
+            Command::If { .. } => open_ifs.push(command.span),
+            Command::EndIf if open_ifs.pop().is_none() => report(
+                command.span,
+                Severity::Error,
+                Problem::UnmatchedEndIf,
+            ),
+
+            _ => {}
+        }
+    }
+
+    for span in open_ifs {
+        report(span, Severity::Error, Problem::UnterminatedIf);
+    }
+}
+
+/// Report code which does nothing useful, all the problems
+/// are warnings
+fn lint(index: &NovelIndex, findings: &mut Vec<Finding>) {
+    let graph = ControlFlowGraph::build(index);
+    let mut warn = |script: &Path, span, problem| {
+        findings.push(Finding {
+            file: Path::new("script").join(script),
+            span,
+            severity: Severity::Warning,
+            problem,
+        })
+    };
+
+    let mut targeted = BTreeSet::new();
+    let mut set_variables = BTreeSet::new();
+    for script in index.scripts() {
+        for jump in &script.jumps {
+            targeted.insert((jump.file.as_path(), jump.label.as_deref()));
+        }
+
+        for command in &script.commands {
+            if let Command::Goto(label) = &command.inner {
+                targeted.insert((script.path.as_path(), Some(label)));
+            }
+
+            set_variables.extend(
+                variables::accesses(&command.inner)
+                    .into_iter()
+                    .filter(|(_, kind, _)| *kind == AccessKind::Write)
+                    .map(|(name, ..)| name),
+            );
+        }
+    }
+
+    let mut targets = vec![0_usize; graph.blocks().len()];
+    for block in graph.blocks() {
+        for edge in &block.edges {
+            if let Target::Block(target) = edge.target {
+                targets[target] += 1;
+            }
+        }
+    }
+
+    let has_entry = index.script(MAIN_SCRIPT).is_some();
+    for (id, block) in graph.blocks().iter().enumerate() {
+        let Some(span) = block.span else {
+            continue;
+        };
+
+        if let Some(label) = &block.label {
+            if !targeted.contains(&(block.file.as_path(), Some(label))) {
+                warn(
+                    &block.file,
+                    span,
+                    Problem::UnusedLabel(label.clone()),
+                );
+            }
+        }
+
+        if block.commands.start == 0 {
+            if has_entry && !graph.is_reachable(id) {
+                warn(&block.file, span, Problem::UnreachableScript);
+            }
+        } else if targets[id] == 0 && block.label.is_none() {
+            // unreachable labeled blocks are reported as unused
+            // labels
+            warn(&block.file, span, Problem::UnreachableCode);
+        }
+    }
+
+    for script in index.scripts() {
+        for command in &script.commands {
+            let Command::If { name, rhs, .. } = &command.inner else {
+                continue;
+            };
+
+            let rhs = match rhs {
+                IfRhs::Variable(rhs) => Some(rhs),
+                IfRhs::Number(..) => None,
+            };
+            for name in [Some(name), rhs].into_iter().flatten() {
+                if !set_variables.contains(name.as_str()) {
+                    warn(
+                        &script.path,
+                        command.span,
+                        Problem::UnsetVariable(name.clone()),
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for ResourceKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Background => "background",
+            Self::Foreground => "foreground",
+            Self::Sound => "sound",
+            Self::Music => "music",
+        })
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Unreadable(error) => {
+                write!(f, "script can't be read: {error}")
+            }
+            Self::Parse(error) => {
+                write!(f, "{}: `{}`", error.error, error.text)
+            }
+            Self::MissingResource { kind, path } => {
+                write!(f, "{kind} {} was not found", path.display())
+            }
+            Self::UndefinedLabel(label) => {
+                write!(f, "label {label} is not defined")
+            }
+            Self::MissingScript(file) => {
+                write!(f, "script {} was not found", file.display())
+            }
+            Self::MissingJumpLabel { file, label } => write!(
+                f,
+                "label {label} is not defined in {}",
+                file.display()
+            ),
+            Self::UnmatchedEndIf => f.write_str("fi without matching if"),
+            Self::UnterminatedIf => f.write_str("if without matching fi"),
+            Self::UnusedLabel(label) => {
+                write!(f, "label {label} is never used")
+            }
+            Self::UnreachableCode => f.write_str("code is never executed"),
+            Self::UnreachableScript => {
+                write!(f, "script is not reachable from {MAIN_SCRIPT}")
+            }
+            Self::UnsetVariable(name) => {
+                write!(f, "variable {name} is never set")
+            }
+            Self::Info(InfoParseError::MalformedLine { text, .. }) => {
+                write!(
+                    f,
+                    "expected key=value, found {text}, line is skipped"
+                )
+            }
+            Self::Info(InfoParseError::DuplicateKey { key, .. }) => {
+                write!(
+                    f,
+                    "key {key} is specified twice, the last value is used"
+                )
+            }
+            Self::Info(error) => write!(f, "{error}"),
+        }
+    }
+}
+
+/// Formats as `<path>:<line>:<column>: <severity>:
+/// <problem>`
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}: {}: {}",
+            self.file.display(),
+            self.span,
+            self.severity,
+            self.problem
+        )
+    }
+}
@@ -0,0 +1,390 @@
+use {
+    crate::{
+        graph::{
+            ControlFlowGraph,
+            Target,
+        },
+        index::NovelIndex,
+        interpreter::{
+            MAIN_SCRIPT,
+            SELECTED_VARIABLE,
+        },
+    },
+    nds_parser::{
+        command::{
+            ChoiceOption,
+            Command,
+            IfRhs,
+            VariableStorageType,
+        },
+        span::Span,
+        text::{
+            Text,
+            TextType,
+        },
+    },
+    std::{
+        collections::{
+            BTreeMap,
+            BTreeSet,
+        },
+        fmt,
+        path::{
+            Path,
+            PathBuf,
+        },
+    },
+};
+
+/// Short names like `x` and `y` differ by one edit but are
+/// unlikely to be typos
+pub const TYPO_MIN_LENGTH: usize = 3;
+
+/// Variables written on every path to the end of the block,
+/// `None` for the blocks which were not visited yet
+type Written<'a> = Vec<Option<BTreeSet<&'a str>>>;
+
+/// Where the variable is stored, determined by its writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VariableStorage {
+    Local,
+    Global,
+
+    /// Written both with `setvar` and `gsetvar`
+    Mixed,
+
+    /// Never written
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AccessKind {
+    Read,
+    Write,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Access {
+    /// Script path relative to the script directory
+    pub file: PathBuf,
+    pub span: Span,
+    pub kind: AccessKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VariableUsage {
+    pub storage: VariableStorage,
+
+    /// Accesses in the order of scripts and commands
+    pub accesses: Vec<Access>,
+
+    /// Reads which may happen before any write on some
+    /// path from the start of the story. Not computed for
+    /// global variables since they are kept between
+    /// playthroughs
+    pub read_before_write: Vec<Access>,
+}
+
+/// Usage of all variables of the novel
+#[derive(Debug, Clone, Default)]
+pub struct VariableAnalysis {
+    pub variables: BTreeMap<String, VariableUsage>,
+
+    /// Pairs of names which differ by one edit, names
+    /// shorter than [`TYPO_MIN_LENGTH`] are not checked
+    pub typo_suspects: Vec<(String, String)>,
+}
+
+impl VariableUsage {
+    pub fn reads(&self) -> impl Iterator<Item = &Access> + '_ {
+        self.accesses
+            .iter()
+            .filter(|access| access.kind == AccessKind::Read)
+    }
+
+    pub fn writes(&self) -> impl Iterator<Item = &Access> + '_ {
+        self.accesses
+            .iter()
+            .filter(|access| access.kind == AccessKind::Write)
+    }
+}
+
+impl VariableAnalysis {
+    pub fn analyze(index: &NovelIndex) -> Self {
+        let mut analysis = Self::default();
+
+        for script in index.scripts() {
+            for command in &script.commands {
+                for (name, kind, storage) in accesses(&command.inner) {
+                    let usage = analysis
+                        .variables
+                        .entry(name.to_owned())
+                        .or_insert_with(|| VariableUsage {
+                            storage: VariableStorage::Unknown,
+                            accesses: Vec::new(),
+                            read_before_write: Vec::new(),
+                        });
+
+                    if let Some(storage) = storage {
+                        usage.storage = usage.storage.with(storage);
+                    }
+                    usage.accesses.push(Access {
+                        file: script.path.clone(),
+                        span: command.span,
+                        kind,
+                    });
+                }
+            }
+        }
+
+        analysis.find_reads_before_writes(index);
+
+        let names: Vec<&String> = analysis
+            .variables
+            .keys()
+            .filter(|name| name.chars().count() >= TYPO_MIN_LENGTH)
+            .collect();
+        for (n, a) in names.iter().enumerate() {
+            for b in &names[n + 1..] {
+                if is_one_edit_apart(a, b) {
+                    analysis
+                        .typo_suspects
+                        .push(((*a).clone(), (*b).clone()));
+                }
+            }
+        }
+
+        analysis
+    }
+
+    /// Must-be-written dataflow analysis over the control
+    /// flow graph
+    fn find_reads_before_writes(&mut self, index: &NovelIndex) {
+        let graph = ControlFlowGraph::build(index);
+        let blocks = graph.blocks();
+        let commands = |id: usize| {
+            let block = &blocks[id];
+            let script = index
+                .script(&block.file)
+                .expect("blocks are built from the index");
+
+            (&block.file, &script.commands[block.commands.clone()])
+        };
+
+        let mut predecessors = vec![Vec::new(); blocks.len()];
+        for (id, block) in blocks.iter().enumerate() {
+            for edge in &block.edges {
+                if let Target::Block(target) = edge.target {
+                    predecessors[target].push(id);
+                }
+            }
+        }
+
+        let entry = blocks.iter().position(|block| {
+            block.file == Path::new(MAIN_SCRIPT)
+                && block.commands.start == 0
+        });
+        let mut written: Written = vec![None; blocks.len()];
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for id in
+                (0..blocks.len()).filter(|&id| graph.is_reachable(id))
+            {
+                let Some(mut set) =
+                    join(entry, id, &predecessors, &written)
+                else {
+                    continue;
+                };
+                for command in commands(id).1 {
+                    for (name, kind, _) in accesses(&command.inner) {
+                        if kind == AccessKind::Write {
+                            set.insert(name);
+                        }
+                    }
+                }
+
+                if written[id].as_ref() != Some(&set) {
+                    written[id] = Some(set);
+                    changed = true;
+                }
+            }
+        }
+
+        for id in (0..blocks.len()).filter(|&id| graph.is_reachable(id)) {
+            let mut set = join(entry, id, &predecessors, &written)
+                .unwrap_or_default();
+            let (file, commands) = commands(id);
+
+            for command in commands {
+                for (name, kind, _) in accesses(&command.inner) {
+                    match kind {
+                        AccessKind::Write => {
+                            set.insert(name);
+                        }
+                        AccessKind::Read if !set.contains(name) => {
+                            let Some(usage) = self.variables.get_mut(name)
+                            else {
+                                continue;
+                            };
+                            if matches!(
+                                usage.storage,
+                                VariableStorage::Global
+                                    | VariableStorage::Mixed
+                            ) {
+                                continue;
+                            }
+
+                            let access = Access {
+                                file: file.clone(),
+                                span: command.span,
+                                kind,
+                            };
+                            if !usage.read_before_write.contains(&access) {
+                                usage.read_before_write.push(access);
+                            }
+                        }
+                        AccessKind::Read => {}
+                    }
+                }
+            }
+        }
+
+        for usage in self.variables.values_mut() {
+            usage
+                .read_before_write
+                .sort_by(|a, b| (&a.file, a.span).cmp(&(&b.file, b.span)));
+        }
+    }
+}
+
+/// Variables written on every path to the beginning of the
+/// block
+fn join<'a>(
+    entry: Option<usize>,
+    block: usize,
+    predecessors: &[Vec<usize>],
+    written: &Written<'a>,
+) -> Option<BTreeSet<&'a str>> {
+    if Some(block) == entry {
+        return Some(BTreeSet::new());
+    }
+
+    predecessors[block]
+        .iter()
+        .filter_map(|&p| written[p].clone())
+        .reduce(|a, b| a.intersection(&b).copied().collect())
+}
+
+impl VariableStorage {
+    fn with(self, storage: VariableStorageType) -> Self {
+        match (self, storage) {
+            (Self::Unknown | Self::Local, VariableStorageType::Local) => {
+                Self::Local
+            }
+            (
+                Self::Unknown | Self::Global,
+                VariableStorageType::Global,
+            ) => Self::Global,
+            _ => Self::Mixed,
+        }
+    }
+}
+
+/// Variables accessed by the command in the order of
+/// access, including the implicit write of the
+/// [`SELECTED_VARIABLE`]. Storage is known for writes only
+pub(crate) fn accesses(
+    command: &Command,
+) -> Vec<(&str, AccessKind, Option<VariableStorageType>)> {
+    fn read(
+        name: &str,
+    ) -> (&str, AccessKind, Option<VariableStorageType>) {
+        (name, AccessKind::Read, None)
+    }
+
+    match command {
+        Command::SetVar { name, storage, .. } => {
+            vec![(name.as_str(), AccessKind::Write, Some(*storage))]
+        }
+        Command::Random { variable, .. } => vec![(
+            variable.as_str(),
+            AccessKind::Write,
+            Some(VariableStorageType::Local),
+        )],
+        Command::If { name, rhs, .. } => match rhs {
+            IfRhs::Variable(rhs) => vec![read(name), read(rhs)],
+            IfRhs::Number(..) => vec![read(name)],
+        },
+        Command::Choice { options } => options
+            .iter()
+            .filter_map(|option| match option {
+                ChoiceOption::Variable(name) => Some(read(name)),
+                ChoiceOption::Option(..) => None,
+            })
+            .chain([(
+                SELECTED_VARIABLE,
+                AccessKind::Write,
+                Some(VariableStorageType::Local),
+            )])
+            .collect(),
+        Command::Text(Text::Spans { spans, .. }) => spans
+            .iter()
+            .filter_map(|span| match &span.text {
+                TextType::Variable(name) => Some(read(name)),
+                TextType::Plain(..) => None,
+            })
+            .collect(),
+
+        _ => Vec::new(),
+    }
+}
+
+/// Whether the Levenshtein distance between the names is 1
+fn is_one_edit_apart(a: &str, b: &str) -> bool {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let (short, long) = if a.len() <= b.len() {
+        (&a, &b)
+    } else {
+        (&b, &a)
+    };
+
+    let prefix = short
+        .iter()
+        .zip(long.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let rest = |chars: &[char], skip| chars[prefix + skip..].to_vec();
+
+    match long.len() - short.len() {
+        0 => prefix < short.len() && rest(short, 1) == rest(long, 1),
+        1 => rest(short, 0) == rest(long, 1),
+        _ => false,
+    }
+}
+
+impl fmt::Display for VariableStorage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Local => "local",
+            Self::Global => "global",
+            Self::Mixed => "local and global",
+            Self::Unknown => "never written",
+        })
+    }
+}
+
+/// Formats as `script/<path>:<line>:<column>`
+impl fmt::Display for Access {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}",
+            Path::new("script").join(&self.file).display(),
+            self.span
+        )
+    }
+}
@@ -0,0 +1,318 @@
+use {
+    crate::{
+        error::VndsSaveError,
+        ini,
+        interpreter::Scene,
+        novel::Novel,
+        save::SaveState,
+        xml::{
+            self,
+            Element,
+        },
+    },
+    std::{
+        collections::BTreeMap,
+        fmt,
+        path::PathBuf,
+        str::FromStr,
+    },
+};
+
+/// Name of the global variables file in the NovelDS save
+/// directory, XML document with the `<global>` root holding
+/// `<var>` elements
+pub const GLOBAL_FILE: &str = "global.sav";
+
+/// Contents of the NovelDS save file: XML document with the
+/// `<save>` root holding `<script>`, `<variables>` and
+/// `<state>` elements
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VndsSave {
+    /// Path of the script relative to the script directory
+    pub file: PathBuf,
+
+    /// 1-based line of the script to continue from
+    pub position: usize,
+
+    /// Date in the form it was stored, it is only shown to
+    /// the player
+    pub date: Option<String>,
+
+    pub variables: BTreeMap<String, u16>,
+
+    /// Names of the variables which are not integers, e.g.
+    /// strings. They are not supported and are dropped
+    pub skipped: Vec<String>,
+
+    pub music: Option<PathBuf>,
+    pub background: Option<PathBuf>,
+    pub sprites: Vec<(PathBuf, (u16, u16))>,
+}
+
+impl VndsSave {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, VndsSaveError> {
+        ini::decode(bytes).parse()
+    }
+
+    /// Convert to the save of this engine, position is
+    /// mapped to the first command starting at or after
+    /// the saved line
+    pub fn to_save_state(
+        &self,
+        novel: &Novel,
+    ) -> Result<SaveState, VndsSaveError> {
+        let script = novel.try_load_script(&self.file)?;
+        let cursor = (0..script.len())
+            .find(|&index| {
+                script
+                    .span(index)
+                    .is_some_and(|span| span.line >= self.position)
+            })
+            .unwrap_or(script.len());
+
+        Ok(SaveState {
+            file: self.file.clone(),
+            cursor,
+            variables: self.variables.clone(),
+            scene: Scene {
+                background: self.background.clone(),
+                foreground: self.sprites.clone(),
+                music: self.music.clone(),
+                history: Vec::new(),
+            },
+            random: None,
+        })
+    }
+
+    /// Convert the save of this engine, text history is
+    /// lost since NovelDS does not store it
+    pub fn from_save_state(
+        save: &SaveState,
+        novel: &Novel,
+    ) -> Result<Self, VndsSaveError> {
+        let script = novel.try_load_script(&save.file)?;
+        let position = match script.span(save.cursor) {
+            Some(span) => span.line,
+            None => script
+                .len()
+                .checked_sub(1)
+                .and_then(|last| script.span(last))
+                .map_or(1, |span| span.line + 1),
+        };
+
+        Ok(Self {
+            file: save.file.clone(),
+            position,
+            date: None,
+            variables: save.variables.clone(),
+            skipped: Vec::new(),
+            music: save.scene.music.clone(),
+            background: save.scene.background.clone(),
+            sprites: save.scene.foreground.clone(),
+        })
+    }
+}
+
+/// Variables of the NovelDS `global.sav`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VndsGlobal {
+    pub variables: BTreeMap<String, u16>,
+
+    /// See [`VndsSave::skipped`]
+    pub skipped: Vec<String>,
+}
+
+/// Read NovelDS `global.sav`
+pub fn read_global(bytes: &[u8]) -> Result<VndsGlobal, VndsSaveError> {
+    let root = parse_root(&ini::decode(bytes), "global")?;
+    let (variables, skipped) = read_variables(&root)?;
+
+    Ok(VndsGlobal { variables, skipped })
+}
+
+/// Write variables in the NovelDS `global.sav` format
+pub fn write_global(variables: &BTreeMap<String, u16>) -> String {
+    let root = variables
+        .iter()
+        .fold(Element::new("global"), |root, (name, value)| {
+            root.with_child(variable(name, *value))
+        });
+
+    format!("{root}\n")
+}
+
+fn parse_root(
+    source: &str,
+    expected: &'static str,
+) -> Result<Element, VndsSaveError> {
+    let root = xml::parse(source)
+        .map_err(|error| VndsSaveError::Xml { offset: error.0 })?;
+    if root.name != expected {
+        return Err(VndsSaveError::UnexpectedRoot {
+            expected,
+            found: root.name,
+        });
+    }
+
+    Ok(root)
+}
+
+/// Integer variables and the names of the skipped ones
+fn read_variables(
+    parent: &Element,
+) -> Result<(BTreeMap<String, u16>, Vec<String>), VndsSaveError> {
+    let mut variables = BTreeMap::new();
+    let mut skipped = Vec::new();
+
+    for var in parent.children("var") {
+        let name = var
+            .attribute("name")
+            .ok_or(VndsSaveError::MissingElement("var name"))?;
+        let value = var.attribute("value").unwrap_or_default();
+
+        match var.attribute("type").unwrap_or("int") {
+            "int" => {
+                variables
+                    .insert(name.to_owned(), parse_value(name, value)?);
+            }
+            _ => skipped.push(name.to_owned()),
+        }
+    }
+
+    Ok((variables, skipped))
+}
+
+fn parse_value<T: FromStr>(
+    name: &str,
+    value: &str,
+) -> Result<T, VndsSaveError> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| VndsSaveError::InvalidValue {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        })
+}
+
+fn variable(name: &str, value: u16) -> Element {
+    Element::new("var")
+        .with_attribute("name", name)
+        .with_attribute("type", "int")
+        .with_attribute("value", &value.to_string())
+}
+
+/// Empty path elements mean that nothing is shown
+fn path(element: Option<&Element>) -> Option<PathBuf> {
+    element
+        .filter(|e| !e.text.is_empty() && e.text != "~")
+        .map(|e| PathBuf::from(&e.text))
+}
+
+impl FromStr for VndsSave {
+    type Err = VndsSaveError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let root = parse_root(s, "save")?;
+        let script = root
+            .child("script")
+            .ok_or(VndsSaveError::MissingElement("script"))?;
+        let file = script
+            .child("file")
+            .ok_or(VndsSaveError::MissingElement("file"))?;
+        let position = script
+            .child("position")
+            .ok_or(VndsSaveError::MissingElement("position"))?;
+
+        let mut save = Self {
+            file: PathBuf::from(&file.text),
+            position: parse_value("position", &position.text)?,
+            date: root.child("date").map(|date| date.text.clone()),
+            ..Self::default()
+        };
+
+        if let Some(variables) = root.child("variables") {
+            (save.variables, save.skipped) = read_variables(variables)?;
+        }
+
+        if let Some(state) = root.child("state") {
+            save.music = path(state.child("music"));
+            save.background = path(state.child("background"));
+
+            for sprite in state
+                .child("sprites")
+                .into_iter()
+                .flat_map(|sprites| sprites.children("sprite"))
+            {
+                let coordinate = |name| {
+                    parse_value(
+                        name,
+                        sprite.attribute(name).unwrap_or("0"),
+                    )
+                };
+                let path = sprite
+                    .attribute("path")
+                    .ok_or(VndsSaveError::MissingElement("sprite path"))?;
+
+                save.sprites.push((
+                    path.into(),
+                    (coordinate("x")?, coordinate("y")?),
+                ));
+            }
+        }
+
+        Ok(save)
+    }
+}
+
+impl fmt::Display for VndsSave {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn text(name: &str, text: impl fmt::Display) -> Element {
+            Element::new(name).with_text(&text.to_string())
+        }
+
+        let mut root = Element::new("save").with_child(
+            Element::new("script")
+                .with_child(text("file", self.file.display()))
+                .with_child(text("position", self.position)),
+        );
+        if let Some(date) = &self.date {
+            root = root.with_child(text("date", date));
+        }
+
+        root = root.with_child(self.variables.iter().fold(
+            Element::new("variables"),
+            |variables, (name, value)| {
+                variables.with_child(variable(name, *value))
+            },
+        ));
+
+        let path = |path: &Option<PathBuf>| {
+            path.as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default()
+        };
+        let sprites = self.sprites.iter().fold(
+            Element::new("sprites"),
+            |sprites, (file, (x, y))| {
+                sprites.with_child(
+                    Element::new("sprite")
+                        .with_attribute(
+                            "path",
+                            &file.display().to_string(),
+                        )
+                        .with_attribute("x", &x.to_string())
+                        .with_attribute("y", &y.to_string()),
+                )
+            },
+        );
+        root = root.with_child(
+            Element::new("state")
+                .with_child(text("music", path(&self.music)))
+                .with_child(text("background", path(&self.background)))
+                .with_child(sprites),
+        );
+
+        writeln!(f, "{root}")
+    }
+}
@@ -0,0 +1,292 @@
+use std::fmt::{
+    self,
+    Write,
+};
+
+/// Element of the XML document. Only the subset used by the
+/// NovelDS files is supported: no namespaces, DTDs or CDATA
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+
+    /// Concatenated text content, trimmed
+    pub text: String,
+}
+
+/// Byte offset where the document stopped making sense
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SyntaxError(pub usize);
+
+impl Element {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            ..Self::default()
+        }
+    }
+
+    pub fn with_attribute(mut self, name: &str, value: &str) -> Self {
+        self.attributes
+            .push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn with_text(mut self, text: &str) -> Self {
+        self.text = text.to_owned();
+        self
+    }
+
+    pub fn with_child(mut self, child: Element) -> Self {
+        self.children.push(child);
+        self
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| v.as_str())
+    }
+
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|c| c.name == name)
+    }
+
+    pub fn children<'a>(
+        &'a self,
+        name: &'a str,
+    ) -> impl Iterator<Item = &'a Element> {
+        self.children
+            .iter()
+            .filter(move |c| c.name == name)
+    }
+}
+
+/// Parse the document and return its root element
+pub(crate) fn parse(source: &str) -> Result<Element, SyntaxError> {
+    let mut parser = Parser { source, offset: 0 };
+
+    parser.skip_misc()?;
+    let root = parser.element()?;
+    parser.skip_misc()?;
+
+    if parser.offset == source.len() {
+        Ok(root)
+    } else {
+        Err(parser.error())
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    offset: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn rest(&self) -> &'a str {
+        &self.source[self.offset..]
+    }
+
+    fn error(&self) -> SyntaxError {
+        SyntaxError(self.offset)
+    }
+
+    fn eat(&mut self, prefix: &str) -> bool {
+        let found = self.rest().starts_with(prefix);
+        if found {
+            self.offset += prefix.len();
+        }
+
+        found
+    }
+
+    fn expect(&mut self, prefix: &str) -> Result<(), SyntaxError> {
+        if self.eat(prefix) {
+            Ok(())
+        } else {
+            Err(self.error())
+        }
+    }
+
+    /// Consume everything up to and including `end`,
+    /// returns consumed part without the `end`
+    fn until(&mut self, end: &str) -> Result<&'a str, SyntaxError> {
+        let rest = self.rest();
+        let length = rest.find(end).ok_or_else(|| self.error())?;
+        self.offset += length + end.len();
+
+        Ok(&rest[..length])
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.offset += rest.len() - rest.trim_start().len();
+    }
+
+    /// Skip whitespace, comments and processing
+    /// instructions
+    fn skip_misc(&mut self) -> Result<(), SyntaxError> {
+        loop {
+            self.skip_whitespace();
+            if self.eat("<?") {
+                self.until("?>")?;
+            } else if self.eat("<!--") {
+                self.until("-->")?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<&'a str, SyntaxError> {
+        let rest = self.rest();
+        let length = rest
+            .find(|c: char| {
+                !(c.is_alphanumeric()
+                    || matches!(c, '_' | '-' | '.' | ':'))
+            })
+            .unwrap_or(rest.len());
+        if length == 0 {
+            return Err(self.error());
+        }
+        self.offset += length;
+
+        Ok(&rest[..length])
+    }
+
+    fn element(&mut self) -> Result<Element, SyntaxError> {
+        self.expect("<")?;
+        let mut element = Element::new(self.name()?);
+
+        loop {
+            self.skip_whitespace();
+            if self.eat("/>") {
+                return Ok(element);
+            }
+            if self.eat(">") {
+                break;
+            }
+
+            let name = self.name()?;
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+
+            let quote = if self.eat("\"") {
+                "\""
+            } else {
+                self.expect("'")?;
+                "'"
+            };
+            let start = self.offset;
+            let value = self.until(quote)?;
+            element.attributes.push((
+                name.to_owned(),
+                unescape(value).ok_or(SyntaxError(start))?,
+            ));
+        }
+
+        let mut text = String::new();
+        loop {
+            let start = self.offset;
+            let rest = self.rest();
+            let length = rest.find('<').ok_or_else(|| self.error())?;
+            text.push_str(
+                &unescape(&rest[..length]).ok_or(SyntaxError(start))?,
+            );
+            self.offset += length;
+
+            if self.eat("</") {
+                if self.name()? != element.name {
+                    return Err(SyntaxError(start + length));
+                }
+                self.skip_whitespace();
+                self.expect(">")?;
+                break;
+            } else if self.eat("<!--") {
+                self.until("-->")?;
+            } else {
+                element.children.push(self.element()?);
+            }
+        }
+        element.text = text.trim().to_owned();
+
+        Ok(element)
+    }
+}
+
+/// Replace entity and character references
+fn unescape(text: &str) -> Option<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        let end = rest[start..].find(';')? + start;
+
+        let entity = &rest[start + 1..end];
+        result.push(match entity {
+            "amp" => '&',
+            "lt" => '<',
+            "gt" => '>',
+            "quot" => '"',
+            "apos" => '\'',
+            _ => {
+                let code = match entity.strip_prefix("#x") {
+                    Some(hex) => u32::from_str_radix(hex, 16),
+                    None => entity.strip_prefix('#')?.parse(),
+                };
+                char::from_u32(code.ok()?)?
+            }
+        });
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+
+    Some(result)
+}
+
+fn escape(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    for c in text.chars() {
+        match c {
+            '&' => f.write_str("&amp;")?,
+            '<' => f.write_str("&lt;")?,
+            '>' => f.write_str("&gt;")?,
+            '"' => f.write_str("&quot;")?,
+            c => f.write_char(c)?,
+        }
+    }
+
+    Ok(())
+}
+
+/// Writes the element indented by two spaces per level
+impl fmt::Display for Element {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let depth = f.width().unwrap_or(0);
+        write!(f, "{:depth$}<{}", "", self.name)?;
+        for (name, value) in &self.attributes {
+            write!(f, " {name}=\"")?;
+            escape(f, value)?;
+            f.write_char('"')?;
+        }
+
+        if self.children.is_empty() && self.text.is_empty() {
+            return f.write_str(" />");
+        }
+
+        f.write_char('>')?;
+        if self.children.is_empty() {
+            escape(f, &self.text)?;
+            return write!(f, "</{}>", self.name);
+        }
+
+        for child in &self.children {
+            writeln!(f)?;
+            write!(f, "{child:0$}", depth + 2)?;
+        }
+        write!(f, "\n{:depth$}</{}>", "", self.name)
+    }
+}
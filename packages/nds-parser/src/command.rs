@@ -5,6 +5,7 @@ use {
     },
     std::{
         convert::Infallible,
+        fmt,
         ops::RangeInclusive,
         path::PathBuf,
         str::FromStr,
@@ -32,6 +33,16 @@ pub enum IfRhs {
     Variable(String),
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ComparisonOperator {
+    Eq,
+    NotEq,
+    Lt,
+    LtOrEq,
+    Gt,
+    GtOrEq,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum VariableModifier {
     Assign,
@@ -92,6 +103,7 @@ pub enum Command {
 
     If {
         name: String,
+        operator: ComparisonOperator,
         rhs: IfRhs,
     },
 
@@ -115,6 +127,10 @@ pub enum Command {
     ClearText(ClearTextType),
 
     EndIf,
+
+    /// Placeholder for the command which failed to parse in
+    /// the lenient mode, holds the source text
+    Invalid(String),
 }
 
 impl FromStr for VariableModifier {
@@ -139,6 +155,41 @@ impl FromStr for VariableModifier {
     }
 }
 
+impl ComparisonOperator {
+    /// Compare `lhs` with `rhs` using the operator
+    pub fn evaluate<T: Ord>(self, lhs: T, rhs: T) -> bool {
+        match self {
+            Self::Eq => lhs == rhs,
+            Self::NotEq => lhs != rhs,
+            Self::Lt => lhs < rhs,
+            Self::LtOrEq => lhs <= rhs,
+            Self::Gt => lhs > rhs,
+            Self::GtOrEq => lhs >= rhs,
+        }
+    }
+}
+
+impl FromStr for ComparisonOperator {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "==" => Self::Eq,
+            "!=" => Self::NotEq,
+            "<" => Self::Lt,
+            "<=" => Self::LtOrEq,
+            ">" => Self::Gt,
+            ">=" => Self::GtOrEq,
+
+            o => {
+                return Err(ParseError::UnknownComparisonOperator {
+                    operator: o.to_owned(),
+                })
+            }
+        })
+    }
+}
+
 impl FromStr for ChoiceOption {
     type Err = Infallible;
 
@@ -150,3 +201,140 @@ impl FromStr for ChoiceOption {
         })
     }
 }
+
+impl fmt::Display for VariableModifier {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Assign => "=",
+            Self::Add => "+",
+            Self::Sub => "-",
+            Self::Gt => ">",
+            Self::Lt => "<",
+            Self::GtOrEq => ">=",
+            Self::LtOrEq => "<=",
+        })
+    }
+}
+
+impl fmt::Display for ComparisonOperator {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Eq => "==",
+            Self::NotEq => "!=",
+            Self::Lt => "<",
+            Self::LtOrEq => "<=",
+            Self::Gt => ">",
+            Self::GtOrEq => ">=",
+        })
+    }
+}
+
+impl fmt::Display for IfRhs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Number(n) => write!(f, "{n}"),
+            Self::Variable(name) => f.write_str(name),
+        }
+    }
+}
+
+impl fmt::Display for ChoiceOption {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Variable(name) => write!(f, "${name}"),
+            Self::Option(option) => f.write_str(option),
+        }
+    }
+}
+
+/// Prints command in the NovelDS script syntax
+impl fmt::Display for Command {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::BgLoad { file, fadetime } => {
+                write!(f, "bgload {} {fadetime}", file.display())
+            }
+            Self::SetImg {
+                file,
+                coordinates: (x, y),
+            } => write!(f, "setimg {} {x} {y}", file.display()),
+
+            Self::Sound(SoundLooping::Infinite { file }) => {
+                write!(f, "sound {} -1", file.display())
+            }
+            Self::Sound(SoundLooping::Count { file, count }) => {
+                write!(f, "sound {} {count}", file.display())
+            }
+            Self::Sound(SoundLooping::StopCurrentlyPlaying) => {
+                f.write_str("sound ~")
+            }
+            Self::Music {
+                file: MusicFile::StopPlaying,
+            } => f.write_str("music ~"),
+            Self::Music {
+                file: MusicFile::Path(path),
+            } => write!(f, "music {}", path.display()),
+
+            Self::Choice { options } => {
+                f.write_str("choice ")?;
+                for (index, option) in options.iter().enumerate() {
+                    if index != 0 {
+                        f.write_str("|")?;
+                    }
+                    write!(f, "{option}")?;
+                }
+
+                Ok(())
+            }
+
+            Self::SetVar {
+                name,
+                accumulator,
+                modifier,
+                storage,
+            } => write!(
+                f,
+                "{} {name} {modifier} {accumulator}",
+                match storage {
+                    VariableStorageType::Global => "gsetvar",
+                    VariableStorageType::Local => "setvar",
+                }
+            ),
+            Self::If {
+                name,
+                operator,
+                rhs,
+            } => write!(f, "if {name} {operator} {rhs}"),
+
+            Self::Jump { file, label } => {
+                write!(f, "jump {}", file.display())?;
+                if let Some(label) = label {
+                    write!(f, " {label}")?;
+                }
+
+                Ok(())
+            }
+            Self::Delay { frames } => write!(f, "delay {frames}"),
+            Self::Random { variable, range } => write!(
+                f,
+                "random {variable} {} {}",
+                range.start(),
+                range.end()
+            ),
+
+            Self::Text(text) => write!(f, "text {text}"),
+
+            Self::Label(label) => write!(f, "label {label}"),
+            Self::Goto(label) => write!(f, "goto {label}"),
+            Self::ClearText(ClearTextType::FillBottomScreen) => {
+                f.write_str("cleartext")
+            }
+            Self::ClearText(ClearTextType::TextBufferInclHistory) => {
+                f.write_str("cleartext !")
+            }
+
+            Self::EndIf => f.write_str("fi"),
+            Self::Invalid(source) => f.write_str(source),
+        }
+    }
+}
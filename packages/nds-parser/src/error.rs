@@ -1,15 +1,30 @@
-use thiserror::Error;
+use {
+    crate::span::Span,
+    std::fmt,
+    thiserror::Error,
+};
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
 pub enum TextParseError {
     #[error("Specified empty variable name in text")]
     EmptyVariableName,
 
-    #[error("Invalid text color specified")]
-    InvalidColor,
+    /// `offset` is the byte offset of the escape sequence
+    /// in the text
+    #[error("Malformed escape sequence at offset {offset}")]
+    MalformedEscape { offset: usize },
+
+    #[error("Unsupported SGR parameter {parameter} at offset {offset}")]
+    UnsupportedSgrParameter { offset: usize, parameter: u16 },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum RenderError {
+    #[error("Variable {0} is not set")]
+    MissingVariable(String),
 }
 
-#[derive(Debug, Error)]
+#[derive(Debug, Clone, Error)]
 pub enum ParseError {
     #[error("No arguments in commands")]
     NoArguments,
@@ -26,6 +41,9 @@ pub enum ParseError {
     #[error("Unknown variable modifier found: {modifier}")]
     UnknownVariableModifier { modifier: String },
 
+    #[error("Unknown comparison operator found: {operator}")]
+    UnknownComparisonOperator { operator: String },
+
     #[error("Random low ({low}) value is higher than high ({high})")]
     RandomLowIsHigherThanHigh { low: u16, high: u16 },
 
@@ -37,3 +55,55 @@ pub enum ParseError {
     #[error("Failed to parse text: {0}")]
     Text(#[from] TextParseError),
 }
+
+#[derive(Debug, Clone, Error)]
+#[error("{span}: {error}: `{text}`")]
+pub struct LocatedParseError {
+    /// Location of the failed command
+    pub span: Span,
+
+    /// Source text of the failed command
+    pub text: String,
+
+    pub error: ParseError,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Command is likely valid for the engine, but can not
+    /// be understood by the parser
+    Warning,
+
+    /// Command is malformed
+    Error,
+}
+
+/// Problem found while parsing the script in the lenient
+/// mode
+#[derive(Debug, Clone, Error)]
+#[error("{severity}: {error}")]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub error: LocatedParseError,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+impl From<LocatedParseError> for Diagnostic {
+    fn from(error: LocatedParseError) -> Self {
+        Self {
+            severity: match error.error {
+                ParseError::UnknownCommand { .. } => Severity::Warning,
+                _ => Severity::Error,
+            },
+            error,
+        }
+    }
+}
@@ -1,5 +1,7 @@
 pub mod command;
 pub mod parser;
+pub mod printer;
+pub mod span;
 
 pub mod text;
 
@@ -1,7 +1,16 @@
 use {
     crate::{
         command::*,
-        error::ParseError,
+        error::{
+            Diagnostic,
+            LocatedParseError,
+            ParseError,
+            Severity,
+        },
+        span::{
+            Span,
+            Spanned,
+        },
         text::Text,
     },
     std::str::FromStr,
@@ -12,6 +21,25 @@ enum OptionalResult<T, E> {
     None,
 }
 
+/// What to do with the commands which failed to parse in
+/// the lenient mode
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InvalidCommands {
+    /// Drop them from the output
+    Skip,
+
+    /// Substitute them with [`Command::Invalid`], so
+    /// command indices stay stable
+    Placeholder,
+}
+
+/// Result of the lenient parsing
+#[derive(Debug)]
+pub struct ParseOutcome {
+    pub commands: Vec<Spanned<Command>>,
+    pub diagnostics: Vec<Diagnostic>,
+}
+
 pub trait ParseScript {
     type Error;
     type Output;
@@ -19,6 +47,24 @@ pub trait ParseScript {
     fn parse_script(&self) -> Result<Self::Output, Self::Error>
     where
         Self: Sized;
+
+    /// Same as [`ParseScript::parse_script`], but every
+    /// command is annotated with its location in the
+    /// source
+    fn parse_script_spanned(
+        &self,
+    ) -> Result<Vec<Spanned<Command>>, Self::Error>
+    where
+        Self: Sized;
+
+    /// Parse the whole script without stopping on the first
+    /// error, every failure is reported as a diagnostic
+    fn parse_script_lenient(
+        &self,
+        invalid: InvalidCommands,
+    ) -> ParseOutcome
+    where
+        Self: Sized;
 }
 
 impl<T, E> OptionalResult<T, E> {
@@ -41,35 +87,124 @@ impl<T, E> From<Option<Result<T, E>>> for OptionalResult<T, E> {
 }
 
 impl<T: AsRef<str>> ParseScript for T {
-    type Error = ParseError;
+    type Error = LocatedParseError;
     type Output = Vec<Command>;
 
     fn parse_script(&self) -> Result<Self::Output, Self::Error>
     where
         Self: Sized,
     {
-        fn trim_start_non_ascii(line: &str) -> &str {
-            if let Some(c) = line.chars().next() {
-                if c.is_ascii() {
-                    line
-                } else {
-                    trim_start_non_ascii(&line[c.len_utf8()..])
+        self.parse_script_spanned().map(|commands| {
+            commands
+                .into_iter()
+                .map(Spanned::into_inner)
+                .collect()
+        })
+    }
+
+    fn parse_script_spanned(
+        &self,
+    ) -> Result<Vec<Spanned<Command>>, Self::Error>
+    where
+        Self: Sized,
+    {
+        try_collect_vec(source_lines(self.as_ref()).map(|(span, line)| {
+            parse_command(line)
+                .map(|command| Spanned::new(span, command))
+                .map_err(|error| LocatedParseError {
+                    span,
+                    text: line.to_owned(),
+                    error,
+                })
+        }))
+    }
+
+    fn parse_script_lenient(
+        &self,
+        invalid: InvalidCommands,
+    ) -> ParseOutcome
+    where
+        Self: Sized,
+    {
+        let mut outcome = ParseOutcome {
+            commands: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+
+        for (span, line) in source_lines(self.as_ref()) {
+            match parse_command(line) {
+                Ok(command) => {
+                    outcome.commands.push(Spanned::new(span, command))
+                }
+                Err(error) => {
+                    if invalid == InvalidCommands::Placeholder {
+                        outcome.commands.push(Spanned::new(
+                            span,
+                            Command::Invalid(line.to_owned()),
+                        ));
+                    }
+
+                    outcome.diagnostics.push(
+                        LocatedParseError {
+                            span,
+                            text: line.to_owned(),
+                            error,
+                        }
+                        .into(),
+                    );
                 }
-            } else {
-                line
             }
         }
 
-        let lines = self.as_ref().lines().filter_map(|v| {
-            let line = trim_start_non_ascii(v.trim());
-            if line.is_empty() {
-                None
+        outcome
+    }
+}
+
+impl ParseOutcome {
+    /// Whether any of diagnostics has [`Severity::Error`]
+    /// severity
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|d| d.severity == Severity::Error)
+    }
+}
+
+/// Iterate over non-empty script lines along with their
+/// locations
+fn source_lines(source: &str) -> impl Iterator<Item = (Span, &str)> {
+    fn trim_start_non_ascii(line: &str) -> &str {
+        if let Some(c) = line.chars().next() {
+            if c.is_ascii() {
+                line
             } else {
-                Some(line)
+                trim_start_non_ascii(&line[c.len_utf8()..])
             }
-        });
-        try_collect_vec(lines.map(parse_command))
+        } else {
+            line
+        }
     }
+
+    source
+        .lines()
+        .enumerate()
+        .filter_map(|(index, v)| {
+            let trimmed = v.trim();
+            let line = trim_start_non_ascii(trimmed);
+            if line.is_empty() {
+                return None;
+            }
+
+            let offset = (v.len() - v.trim_start().len())
+                + (trimmed.len() - line.len());
+            let span = Span {
+                line: index + 1,
+                column: v[..offset].chars().count() + 1,
+                length: line.chars().count(),
+            };
+
+            Some((span, line))
+        })
 }
 
 fn parse_command(line: &str) -> Result<Command, ParseError> {
@@ -117,11 +252,12 @@ fn parse_command(line: &str) -> Result<Command, ParseError> {
         }
 
         "if" => {
-            let (variable, eq_and_val) = split_once_required(args)?;
-            let (_, value) = split_once_required(eq_and_val)?;
+            let (variable, op_and_val) = split_once_required(args)?;
+            let (operator, value) = split_once_required(op_and_val)?;
 
             Command::If {
                 name: variable.to_owned(),
+                operator: operator.parse()?,
                 rhs: match value.parse() {
                     Ok(v) => IfRhs::Number(v),
                     _ => IfRhs::Variable(value.to_owned()),
@@ -232,12 +368,12 @@ fn split_once_optional(s: &str) -> (&str, Option<&str>) {
     (&left[..left.len() - 1], Some(right))
 }
 
-fn try_collect_vec(
-    commands: impl Iterator<Item = Result<Command, ParseError>>,
-) -> Result<Vec<Command>, ParseError> {
+fn try_collect_vec<T, E>(
+    items: impl Iterator<Item = Result<T, E>>,
+) -> Result<Vec<T>, E> {
     let mut vec = Vec::new();
-    for command in commands {
-        vec.push(command?);
+    for item in items {
+        vec.push(item?);
     }
 
     Ok(vec)
@@ -2,5 +2,7 @@ pub use crate::{
     command::*,
     error::*,
     parser::*,
+    printer::*,
+    span::*,
     text::*,
 };
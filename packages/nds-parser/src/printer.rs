@@ -0,0 +1,22 @@
+use {
+    crate::command::Command,
+    std::fmt::Write,
+};
+
+pub trait PrintScript {
+    /// Print commands back to the NovelDS script source,
+    /// one command per line
+    fn print_script(&self) -> String;
+}
+
+impl<T: AsRef<[Command]>> PrintScript for T {
+    fn print_script(&self) -> String {
+        let mut source = String::new();
+        for command in self.as_ref() {
+            // Writing to the `String` never fails
+            let _ = writeln!(source, "{command}");
+        }
+
+        source
+    }
+}
@@ -0,0 +1,37 @@
+use std::fmt;
+
+/// Location of the command inside the script source
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Span {
+    /// 1-based line number
+    pub line: usize,
+
+    /// 1-based column of the first command character
+    pub column: usize,
+
+    /// Length of the command source in characters
+    pub length: usize,
+}
+
+/// Value annotated with its location in the script source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Spanned<T> {
+    pub span: Span,
+    pub inner: T,
+}
+
+impl<T> Spanned<T> {
+    pub const fn new(span: Span, inner: T) -> Self {
+        Self { span, inner }
+    }
+
+    pub fn into_inner(self) -> T {
+        self.inner
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
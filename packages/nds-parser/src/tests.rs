@@ -3,15 +3,31 @@ use crate::{
         ChoiceOption,
         ClearTextType,
         Command,
+        ComparisonOperator,
         IfRhs,
         MusicFile,
         SoundLooping,
         VariableModifier,
         VariableStorageType,
     },
+    error::{
+        ParseError,
+        RenderError,
+        Severity,
+        TextParseError,
+    },
+    parser::InvalidCommands,
     prelude::ParseScript,
+    printer::PrintScript,
+    span::{
+        Span,
+        Spanned,
+    },
     text::{
-        Foreground,
+        Color,
+        MissingVariable,
+        RenderedSpan,
+        Style,
         Text,
         TextSpan,
         TextType,
@@ -206,11 +222,13 @@ fn test_branch() {
         [
             Command::If {
                 name: "a".to_owned(),
+                operator: ComparisonOperator::Eq,
                 rhs: IfRhs::Variable("b".to_owned())
             },
             Command::EndIf,
             Command::If {
                 name: "a".to_owned(),
+                operator: ComparisonOperator::Eq,
                 rhs: IfRhs::Number(10)
             },
             Command::EndIf,
@@ -218,37 +236,471 @@ fn test_branch() {
     );
 }
 
+#[test]
+fn test_branch_operators() {
+    let operators = [
+        ("==", ComparisonOperator::Eq),
+        ("!=", ComparisonOperator::NotEq),
+        ("<", ComparisonOperator::Lt),
+        ("<=", ComparisonOperator::LtOrEq),
+        (">", ComparisonOperator::Gt),
+        (">=", ComparisonOperator::GtOrEq),
+    ];
+
+    for (source, operator) in operators {
+        let commands = format!("if affection {source} 10")
+            .parse_script()
+            .unwrap();
+
+        assert_eq!(
+            commands,
+            [Command::If {
+                name: "affection".to_owned(),
+                operator,
+                rhs: IfRhs::Number(10)
+            }]
+        );
+    }
+}
+
+#[test]
+fn test_branch_unknown_operator() {
+    let error = "if affection => 10".parse_script().unwrap_err();
+
+    assert!(matches!(
+        error.error,
+        ParseError::UnknownComparisonOperator { operator } if operator == "=>"
+    ));
+}
+
+#[test]
+fn test_comparison_evaluate() {
+    assert!(ComparisonOperator::Eq.evaluate(1, 1));
+    assert!(ComparisonOperator::NotEq.evaluate(1, 2));
+    assert!(ComparisonOperator::Lt.evaluate(1, 2));
+    assert!(ComparisonOperator::LtOrEq.evaluate(2, 2));
+    assert!(ComparisonOperator::Gt.evaluate(3, 2));
+    assert!(ComparisonOperator::GtOrEq.evaluate(2, 2));
+    assert!(!ComparisonOperator::GtOrEq.evaluate(1, 2));
+}
+
 #[test]
 fn test_text() {
     let commands = "text hello $world real world \\x1b[30;1m{$name}-chan"
         .parse_script()
         .unwrap();
 
+    let black = Style {
+        foreground: Some(Color::Black),
+        bold: true,
+        ..Style::default()
+    };
     assert_eq!(
         commands,
-        [Command::Text(Text {
+        [Command::Text(Text::Spans {
             spans: vec![
                 TextSpan {
                     text: TextType::Plain("hello ".to_owned()),
-                    color: Foreground::Regular,
+                    style: Style::default(),
                 },
                 TextSpan {
                     text: TextType::Variable("world".to_owned()),
-                    color: Foreground::Regular,
+                    style: Style::default(),
                 },
                 TextSpan {
                     text: TextType::Plain("real world ".to_owned()),
-                    color: Foreground::Regular,
+                    style: Style::default(),
                 },
                 TextSpan {
                     text: TextType::Variable("name".to_owned()),
-                    color: Foreground::Black,
+                    style: black,
                 },
                 TextSpan {
                     text: TextType::Plain("-chan".to_owned()),
-                    color: Foreground::Black,
+                    style: black,
                 },
-            ]
+            ],
+            click_to_advance: true,
         })]
     );
 }
+
+#[test]
+fn test_text_styles() {
+    let text: Text = "a\\x1b[1;3;91;104mb\\x1b[22;39mc\\x1b[;4md\\e"
+        .parse()
+        .unwrap();
+    let styles = match &text {
+        Text::Spans { spans, .. } => spans
+            .iter()
+            .map(|span| span.style)
+            .collect::<Vec<_>>(),
+        Text::BlankLine { .. } => panic!("text is not blank"),
+    };
+    let bright = Style {
+        foreground: Some(Color::BrightRed),
+        background: Some(Color::BrightBlue),
+        bold: true,
+        italic: true,
+        underline: false,
+    };
+    assert_eq!(
+        styles,
+        [
+            Style::default(),
+            bright,
+            Style {
+                foreground: None,
+                bold: false,
+                ..bright
+            },
+            Style {
+                underline: true,
+                ..Style::default()
+            },
+        ]
+    );
+    assert_eq!(
+        text.to_string(),
+        "a\\x1b[91;104;1;3mb\\x1b[0;104;3mc\\x1b[0;4md\\\\e"
+    );
+
+    for (source, error) in [
+        ("ab\\x1b[31", TextParseError::MalformedEscape { offset: 2 }),
+        ("\\x1b[31;1n", TextParseError::MalformedEscape { offset: 0 }),
+        (
+            "\\x1b[99999m",
+            TextParseError::MalformedEscape { offset: 0 },
+        ),
+        (
+            "a \\x1b[1;38m",
+            TextParseError::UnsupportedSgrParameter {
+                offset: 2,
+                parameter: 38,
+            },
+        ),
+    ] {
+        assert_eq!(source.parse::<Text>().unwrap_err(), error, "{source}");
+    }
+}
+
+#[test]
+fn test_text_escapes() {
+    let plain = |source: &str| match source.parse::<Text>().unwrap() {
+        Text::Spans { spans, .. } => spans
+            .into_iter()
+            .map(|span| match span.text {
+                TextType::Plain(text) => text,
+                TextType::Variable(name) => format!("<{name}>"),
+            })
+            .collect::<Vec<_>>(),
+        Text::BlankLine { .. } => panic!("{source:?} is blank"),
+    };
+
+    assert_eq!(plain("costs \\$5"), ["costs $5"]);
+    assert_eq!(plain("\\{not a variable}"), ["{not a variable}"]);
+    assert_eq!(plain("{plain}"), ["{plain}"]);
+    assert_eq!(plain("back\\\\slash\\nline"), ["back\\slash\nline"]);
+    assert_eq!(plain("\\\\x1b[31m"), ["\\x1b[31m"]);
+    assert_eq!(plain("unknown \\q"), ["unknown \\q"]);
+    assert_eq!(plain("trailing \\"), ["trailing \\"]);
+    assert_eq!(plain("$a\\$b"), ["<a>", "$b"]);
+    assert_eq!(plain("{"), ["{"]);
+
+    for source in ["$5 {x} \\ \n", "\\x1b[31m \\", "{$a}\\", "\\"] {
+        let text = Text::Spans {
+            spans: vec![TextSpan {
+                text: TextType::Plain(source.to_owned()),
+                style: Style::default(),
+            }],
+            click_to_advance: true,
+        };
+        let printed = text.to_string();
+        assert_eq!(printed.parse::<Text>().unwrap(), text, "{printed}");
+    }
+}
+
+#[test]
+fn test_text_variable_terminators() {
+    let spans = |source: &str| match source.parse::<Text>().unwrap() {
+        Text::Spans { spans, .. } => spans
+            .into_iter()
+            .map(|span| span.text)
+            .collect::<Vec<_>>(),
+        Text::BlankLine { .. } => panic!("{source:?} is blank"),
+    };
+    let plain = |text: &str| TextType::Plain(text.to_owned());
+    let variable = |name: &str| TextType::Variable(name.to_owned());
+
+    assert_eq!(
+        spans("Hello $name, how are you"),
+        [plain("Hello "), variable("name"), plain(", how are you")]
+    );
+    assert_eq!(spans("$a.b"), [variable("a"), plain(".b")]);
+    assert_eq!(spans("$a$b"), [variable("a"), variable("b")]);
+    assert_eq!(spans("$a b"), [variable("a"), plain("b")]);
+}
+
+#[test]
+fn test_text_escapes_markers() {
+    for (source, click_to_advance) in [
+        ("@foo", true),
+        ("@foo", false),
+        ("~", true),
+        ("!", true),
+        ("~", false),
+        ("!", false),
+        ("@", true),
+        ("\\x1b[0m", true),
+    ] {
+        let text = Text::Spans {
+            spans: vec![TextSpan {
+                text: TextType::Plain(source.to_owned()),
+                style: Style::default(),
+            }],
+            click_to_advance,
+        };
+        let printed = text.to_string();
+        assert_eq!(printed.parse::<Text>().unwrap(), text, "{printed}");
+    }
+}
+
+#[test]
+fn test_text_markers() {
+    // texts which start with or consist of the line
+    // markers, and how they are printed back
+    for (source, printed) in [
+        ("~", "~"),
+        ("!", "!"),
+        ("@no click", "@no click"),
+        ("@", "@"),
+        ("@~", "@~"),
+        ("@@", "@@"),
+        (" leading space", " leading space"),
+        ("\\x1b[0m@foo", "\\x1b[0m@foo"),
+        ("\\x1b[0m~", "\\x1b[0m~"),
+        ("\\x1b[0m!", "\\x1b[0m!"),
+        ("\\x1b[mbar", "bar"),
+        ("\\x1b[31m@red", "\\x1b[31m@red"),
+        ("\\x1b[0m", "\\x1b[0m"),
+        ("\\x1b[31m", "\\x1b[0m"),
+    ] {
+        let text = source.parse::<Text>().unwrap();
+        assert_eq!(text.to_string(), printed, "{source:?}");
+    }
+
+    assert_eq!(
+        "\\x1b[0m@foo".parse::<Text>().unwrap(),
+        Text::Spans {
+            spans: vec![TextSpan {
+                text: TextType::Plain("@foo".to_owned()),
+                style: Style::default(),
+            }],
+            click_to_advance: true,
+        }
+    );
+}
+
+#[test]
+fn test_text_render() {
+    let text: Text = "a=$a, b={$b}\\x1b[31m!{$c}".parse().unwrap();
+    let variables = std::collections::BTreeMap::from([
+        ("a".to_owned(), 1),
+        ("c".to_owned(), 3),
+    ]);
+    let red = Style {
+        foreground: Some(Color::Red),
+        ..Style::default()
+    };
+    let render = |missing| {
+        text.render(&variables, missing).map(|spans| {
+            spans
+                .into_iter()
+                .map(|span| (span.text, span.style))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    assert_eq!(
+        render(MissingVariable::Placeholder).unwrap(),
+        [
+            ("a=1, b={$b}".to_owned(), Style::default()),
+            ("!3".to_owned(), red)
+        ]
+    );
+    assert_eq!(
+        render(MissingVariable::Empty).unwrap(),
+        [
+            ("a=1, b=".to_owned(), Style::default()),
+            ("!3".to_owned(), red)
+        ]
+    );
+    assert_eq!(
+        render(MissingVariable::Error),
+        Err(RenderError::MissingVariable("b".to_owned()))
+    );
+
+    let blank: Text = "!".parse().unwrap();
+    assert_eq!(
+        blank.render(&variables, MissingVariable::Error),
+        Ok(Vec::<RenderedSpan>::new())
+    );
+}
+
+#[test]
+fn test_spans() {
+    let commands = "label start\n\n  \u{feff}goto start\n\tfi"
+        .parse_script_spanned()
+        .unwrap();
+
+    assert_eq!(
+        commands,
+        [
+            Spanned::new(
+                Span {
+                    line: 1,
+                    column: 1,
+                    length: 11
+                },
+                Command::Label("start".to_owned())
+            ),
+            Spanned::new(
+                Span {
+                    line: 3,
+                    column: 4,
+                    length: 10
+                },
+                Command::Goto("start".to_owned())
+            ),
+            Spanned::new(
+                Span {
+                    line: 4,
+                    column: 2,
+                    length: 2
+                },
+                Command::EndIf
+            ),
+        ]
+    );
+}
+
+#[test]
+fn test_error_location() {
+    let error = "label start\n\n  setimg test.jpg"
+        .parse_script()
+        .unwrap_err();
+
+    assert_eq!(
+        error.span,
+        Span {
+            line: 3,
+            column: 3,
+            length: 15
+        }
+    );
+    assert_eq!(error.text, "setimg test.jpg");
+    assert!(matches!(error.error, ParseError::NotEnoughArguments));
+    assert_eq!(
+        error.to_string(),
+        "3:3: Not enough arguments to command: `setimg test.jpg`"
+    );
+}
+
+#[test]
+fn test_lenient() {
+    let source =
+        "label start\nsetimg test.jpg\nunknown command\ngoto start";
+
+    let outcome = source.parse_script_lenient(InvalidCommands::Skip);
+    assert_eq!(
+        outcome
+            .commands
+            .into_iter()
+            .map(Spanned::into_inner)
+            .collect::<Vec<_>>(),
+        [
+            Command::Label("start".to_owned()),
+            Command::Goto("start".to_owned())
+        ]
+    );
+    assert_eq!(
+        outcome
+            .diagnostics
+            .iter()
+            .map(|d| (d.severity, d.error.span.line))
+            .collect::<Vec<_>>(),
+        [(Severity::Error, 2), (Severity::Warning, 3)]
+    );
+
+    let outcome =
+        source.parse_script_lenient(InvalidCommands::Placeholder);
+    assert!(outcome.has_errors());
+    assert_eq!(
+        outcome.commands[1],
+        Spanned::new(
+            Span {
+                line: 2,
+                column: 1,
+                length: 15
+            },
+            Command::Invalid("setimg test.jpg".to_owned())
+        )
+    );
+    assert_eq!(outcome.commands.len(), 4);
+}
+
+#[test]
+fn test_print_roundtrip() {
+    // every command form and text feature must survive
+    // printing and parsing back
+    for source in [
+        "cleartext\ncleartext !",
+        "random var 0 10",
+        "delay 100",
+        "jump file.scr\njump fuck.scr label",
+        "label test\ngoto test",
+        "choice hello|world|$name",
+        "music bgm.mp3\nmusic ~",
+        "sound bg0.aac -1\nsound ~\nsound waves.aac -1\nsound hit.aac 3",
+        "setvar affection = 10\ngsetvar progress = 0\nsetvar a + \
+         1\nsetvar b - 2",
+        "setimg test.jpg 10 10",
+        "bgload test.jpg 20\nbgload test.jpg",
+        "if a == b\nfi\nif a == 10\nfi",
+        "if a != 1\nif a < 2\nif a <= 3\nif a > 4\nif a >= \
+         5\nfi\nfi\nfi\nfi\nfi",
+        "text hello $world real world \\x1b[30;1m{$name}-chan",
+        "text \\x1b[31;1mred\\x1b[0m regular \\x1b[37;1m$white",
+        "text ~\ntext !\ntext @no click\ntext @\ntext  leading space",
+        "text \\x1b[1;3;4;91;104mall\\x1b[22;23mless\\x1b[39;49;24mnone",
+        "text \\$5 \\{braces} \\\\ \\n \\q {$a}\\\\ trailing \\",
+        "text \\x1b[32mgreen\\x1b[mreset \\x1b[97;40;1mbright\\x1b[1mnot \
+         a \\x1b",
+        "text \\x1b[0m@foo\ntext \\x1b[0m~\ntext \\x1b[0m!\ntext \
+         @@\ntext @~",
+        "text \\x1b[0m\ntext \\x1b[31m\ntext @\\x1b[31m",
+    ] {
+        let commands = source.parse_script().unwrap();
+        let printed = commands.print_script();
+
+        assert_eq!(
+            printed.parse_script().unwrap(),
+            commands,
+            "{source:?} was printed as {printed:?}"
+        );
+    }
+}
+
+#[test]
+fn test_print() {
+    let commands = "bgload test.jpg\nif a >= b\ntext @\\x1b[31;1mred \
+                    {$name}\\x1b[0m.\nsound ~"
+        .parse_script()
+        .unwrap();
+
+    assert_eq!(
+        commands.print_script(),
+        "bgload test.jpg 16\nif a >= b\ntext @\\x1b[31;1mred \
+         {$name}\\x1b[0m.\nsound ~\n"
+    );
+}
@@ -1,23 +1,56 @@
 use {
-    crate::error::TextParseError,
-    std::str::FromStr,
+    crate::error::{
+        RenderError,
+        TextParseError,
+    },
+    std::{
+        collections::{
+            BTreeMap,
+            HashMap,
+        },
+        fmt,
+        str::{
+            Chars,
+            FromStr,
+        },
+    },
 };
 
+/// Text color, in the order of the SGR color codes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
-#[repr(u16)]
-pub enum Foreground {
+pub enum Color {
     /// Actually greish
-    Black = 30,
-
-    Red = 31,
-    Green = 32,
-    Yellow = 33,
-    Blue = 34,
-    Purple = 35,
-    Cyan = 36,
-    White = 37,
+    Black,
+
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Purple,
+    Cyan,
+    White,
+
+    BrightBlack,
+    BrightRed,
+    BrightGreen,
+    BrightYellow,
+    BrightBlue,
+    BrightPurple,
+    BrightCyan,
+    BrightWhite,
+}
 
-    Regular = 39,
+/// Appearance of the text span, set by the SGR escape
+/// sequences `\x1b[<N>;<N>...m`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Style {
+    /// `None` is the default color of the text box
+    pub foreground: Option<Color>,
+    pub background: Option<Color>,
+
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -29,7 +62,7 @@ pub enum TextType {
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TextSpan {
     pub text: TextType,
-    pub color: Foreground,
+    pub style: Style,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -43,99 +76,367 @@ pub enum Text {
     },
 }
 
-// Start: \x1b[<N>;1m
-// Set to regular color: \x1b[0m
-
-macro_rules! define_parsers {
-    ($(
-        let $name:ident $iterator:ident $last:ident $color:ident = $expr:expr
-    )*) => {
-        $(
-            #[allow(clippy::ptr_arg)]
-            fn $name<I>(
-                $iterator: &mut I,
-                $last: &mut String,
-                $color: &mut Foreground,
-            ) -> Result<Option<TextSpan>, TextParseError>
-            where I: Iterator<Item = char>
-            {
-                $expr
+/// Span of the rendered text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RenderedSpan {
+    pub text: String,
+    pub style: Style,
+}
+
+/// What [`VariableLookup::render_variable`] does with the
+/// variable which is not set
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MissingVariable {
+    /// Fail with [`RenderError::MissingVariable`]
+    Error,
+
+    /// Render nothing
+    Empty,
+
+    /// Render the variable as `{$name}`
+    #[default]
+    Placeholder,
+}
+
+/// Source of the variable values for [`Text::render`]
+pub trait VariableLookup {
+    /// Value of the variable, `None` if it is not set
+    fn lookup(&self, name: &str) -> Option<u16>;
+
+    /// Variable as it is shown to the player, empty for the
+    /// [`MissingVariable::Empty`] one
+    fn render_variable(
+        &self,
+        name: &str,
+        missing: MissingVariable,
+    ) -> Result<String, RenderError> {
+        match (self.lookup(name), missing) {
+            (Some(value), _) => Ok(value.to_string()),
+            (None, MissingVariable::Error) => {
+                Err(RenderError::MissingVariable(name.to_owned()))
+            }
+            (None, MissingVariable::Empty) => Ok(String::new()),
+            (None, MissingVariable::Placeholder) => {
+                Ok(format!("{{${name}}}"))
+            }
+        }
+    }
+}
+
+/// Characters which end the `$name` variable
+const NAME_TERMINATORS: &[char] =
+    &[' ', '$', '{', '}', ';', ',', '.', '=', '(', ')', '\\', '/'];
+
+/// Start of the escape sequence as written in the scripts
+const ESCAPE: &str = "\\x1b[";
+
+impl Color {
+    const ALL: [Self; 16] = [
+        Self::Black,
+        Self::Red,
+        Self::Green,
+        Self::Yellow,
+        Self::Blue,
+        Self::Purple,
+        Self::Cyan,
+        Self::White,
+        Self::BrightBlack,
+        Self::BrightRed,
+        Self::BrightGreen,
+        Self::BrightYellow,
+        Self::BrightBlue,
+        Self::BrightPurple,
+        Self::BrightCyan,
+        Self::BrightWhite,
+    ];
+
+    /// Color of the SGR parameter relative to the `base`
+    /// code of the regular colors: 30 for the foreground
+    /// and 40 for the background
+    fn from_parameter(parameter: u16, base: u16) -> Option<Self> {
+        let index = match parameter.checked_sub(base)? {
+            index @ 0..=7 => index,
+            index @ 60..=67 => index - 52,
+            _ => return None,
+        };
+
+        Some(Self::ALL[usize::from(index)])
+    }
+
+    fn parameter(self, base: u16) -> u16 {
+        match self as u16 {
+            index @ 0..=7 => base + index,
+            index => base + index + 52,
+        }
+    }
+}
+
+impl Style {
+    /// Apply the SGR parameter, returns `false` if the
+    /// parameter is not supported
+    fn apply(&mut self, parameter: u16) -> bool {
+        match parameter {
+            0 => *self = Self::default(),
+            1 => self.bold = true,
+            3 => self.italic = true,
+            4 => self.underline = true,
+            22 => self.bold = false,
+            23 => self.italic = false,
+            24 => self.underline = false,
+            39 => self.foreground = None,
+            49 => self.background = None,
+
+            parameter => {
+                if let Some(color) = Color::from_parameter(parameter, 30) {
+                    self.foreground = Some(color);
+                } else if let Some(color) =
+                    Color::from_parameter(parameter, 40)
+                {
+                    self.background = Some(color);
+                } else {
+                    return false;
+                }
+            }
+        }
+
+        true
+    }
+
+    /// SGR parameters which set the style from the default
+    /// one, empty for the default style
+    pub fn parameters(&self) -> Vec<u16> {
+        let mut parameters = Vec::new();
+        parameters
+            .extend(self.foreground.map(|color| color.parameter(30)));
+        parameters
+            .extend(self.background.map(|color| color.parameter(40)));
+        for (enabled, parameter) in
+            [(self.bold, 1), (self.italic, 3), (self.underline, 4)]
+        {
+            if enabled {
+                parameters.push(parameter);
+            }
+        }
+
+        parameters
+    }
+
+    /// Whether switching from `self` to the `next` style
+    /// has to reset something
+    fn needs_reset(&self, next: &Self) -> bool {
+        (self.foreground.is_some() && next.foreground.is_none())
+            || (self.background.is_some() && next.background.is_none())
+            || (self.bold && !next.bold)
+            || (self.italic && !next.italic)
+            || (self.underline && !next.underline)
+    }
+}
+
+/// State of the text parser
+struct Parser<'a> {
+    source: &'a str,
+    chars: Chars<'a>,
+
+    spans: Vec<TextSpan>,
+
+    /// Plain text of the span being parsed
+    last: String,
+    style: Style,
+}
+
+impl<'a> Parser<'a> {
+    fn new(source: &'a str) -> Self {
+        Self {
+            source,
+            chars: source.chars(),
+            spans: Vec::new(),
+            last: String::new(),
+            style: Style::default(),
+        }
+    }
+
+    /// Byte offset of the next character in the text
+    fn offset(&self) -> usize {
+        self.source.len() - self.chars.as_str().len()
+    }
+
+    fn parse(mut self) -> Result<Vec<TextSpan>, TextParseError> {
+        while let Some(chr) = self.chars.next() {
+            match chr {
+                '$' => self.parse_variable()?,
+                '{' => self.parse_braced_variable(),
+                '\\' => self.parse_escape()?,
+
+                chr => self.last.push(chr),
             }
-        )*
-    };
-}
-
-define_parsers! {
-    let parse_variable it __ current_color = {
-        fn can_be_used(c: char) -> bool {
-            !matches!(
-                c,
-                ' '  |
-                '$'  |
-                '{'  |
-                '}'  |
-                ';'  |
-                ','  |
-                '.'  |
-                '='  |
-                '('  |
-                ')'  |
-                '\\' |
-                '/'
-            )
-        }
-
-        let name: String = it.take_while(|&c| can_be_used(c)).collect();
+        }
+
+        self.finish_plain();
+        Ok(self.spans)
+    }
+
+    /// Push the plain text parsed so far as a span
+    fn finish_plain(&mut self) {
+        if !self.last.is_empty() {
+            self.spans.push(TextSpan {
+                text: TextType::Plain(std::mem::take(&mut self.last)),
+                style: self.style,
+            });
+        }
+    }
+
+    fn push_variable(&mut self, name: String) {
+        self.finish_plain();
+        self.spans.push(TextSpan {
+            text: TextType::Variable(name),
+            style: self.style,
+        });
+    }
+
+    /// `$name`, the space which ends the name is dropped,
+    /// any other character ending it is kept in the text
+    fn parse_variable(&mut self) -> Result<(), TextParseError> {
+        let rest = self.chars.as_str();
+        let name = match rest.find(NAME_TERMINATORS) {
+            Some(end) => &rest[..end],
+            None => rest,
+        };
         if name.is_empty() {
-            Err(TextParseError::EmptyVariableName)
-        } else {
-            Ok(TextSpan {
-                text: TextType::Variable(name),
-                color: *current_color,
-            }.into())
+            return Err(TextParseError::EmptyVariableName);
         }
+
+        let rest = &rest[name.len()..];
+        self.chars = rest.strip_prefix(' ').unwrap_or(rest).chars();
+        self.push_variable(name.to_owned());
+        Ok(())
     }
 
-    let parse_color it last current_color = {
-        let until_bracket: String = it.take_while(|&c| c != '[').collect();
-        if until_bracket != "x1b" {
-            last.push('\\');
-            last.push_str(&until_bracket);
-            last.push('[');
-            return Ok(None);
+    /// `{$name}`
+    fn parse_braced_variable(&mut self) {
+        match self.chars.next() {
+            Some('$') => {
+                let name = self
+                    .chars
+                    .by_ref()
+                    .take_while(|&c| c != '}')
+                    .collect();
+                self.push_variable(name);
+            }
+            Some(chr) => {
+                self.last.push('{');
+                self.last.push(chr);
+            }
+            None => self.last.push('{'),
         }
+    }
 
-        let number_str: String = it.take(2).collect();
+    /// `\x1b[<parameters>m` or one of `\$`, `\{`, `\\`,
+    /// `\n`. Unknown escapes and the trailing backslash are
+    /// kept as is
+    fn parse_escape(&mut self) -> Result<(), TextParseError> {
+        let offset = self.offset() - 1;
+        let rest = &self.source[offset..];
+        if !rest.starts_with(ESCAPE) {
+            let escaped = match rest[1..].chars().next() {
+                Some(chr @ ('$' | '{' | '\\')) => chr,
+                Some('n') => '\n',
+                _ => {
+                    self.last.push('\\');
+                    return Ok(());
+                }
+            };
 
-        if number_str == "0m" {
-            *current_color = Foreground::Regular;
-        } else {
-            // skip 3 characters,
-            // I don't care about correctness for the remaining part O_o
-            let _ = it.nth(2);
+            self.chars.next();
+            self.last.push(escaped);
+            return Ok(());
+        }
 
-            *current_color = Foreground::try_from(
-                number_str.parse::<u16>()
-                          .map_err(|_| TextParseError::InvalidColor)?
-            )?;
+        let rest = &rest[ESCAPE.len()..];
+        let length = rest
+            .find(|c: char| !c.is_ascii_digit() && c != ';')
+            .unwrap_or(rest.len());
+        if !rest[length..].starts_with('m') {
+            return Err(TextParseError::MalformedEscape { offset });
         }
 
-        //*current_color = Foreground::try_from(color_number)?;
+        let mut style = self.style;
+        for parameter in rest[..length].split(';') {
+            // empty parameter is the same as 0
+            let parameter = match parameter {
+                "" => 0,
+                parameter => parameter.parse().map_err(|_| {
+                    TextParseError::MalformedEscape { offset }
+                })?,
+            };
+
+            if !style.apply(parameter) {
+                return Err(TextParseError::UnsupportedSgrParameter {
+                    offset,
+                    parameter,
+                });
+            }
+        }
 
-        Ok(None)
+        if style != self.style {
+            self.finish_plain();
+            self.style = style;
+        }
+        self.chars =
+            self.source[offset + ESCAPE.len() + length + 1..].chars();
+
+        Ok(())
     }
+}
 
-    let parse_braced_variable it last current_color = {
-        let cchar = it.next();
-        if !matches!(cchar, Some('$')) {
-            last.push(cchar.unwrap());
-            return Ok(None);
+impl Text {
+    /// Replace variables with their values. Consecutive
+    /// spans of the same style are merged, blank line has
+    /// no spans
+    pub fn render(
+        &self,
+        variables: &impl VariableLookup,
+        missing: MissingVariable,
+    ) -> Result<Vec<RenderedSpan>, RenderError> {
+        let Self::Spans { spans, .. } = self else {
+            return Ok(Vec::new());
+        };
+
+        let mut rendered: Vec<RenderedSpan> = Vec::new();
+        for span in spans {
+            let text = match &span.text {
+                TextType::Plain(text) => text.clone(),
+                TextType::Variable(name) => {
+                    match variables.render_variable(name, missing)? {
+                        text if text.is_empty() => continue,
+                        text => text,
+                    }
+                }
+            };
+
+            match rendered.last_mut() {
+                Some(last) if last.style == span.style => {
+                    last.text.push_str(&text)
+                }
+                _ => rendered.push(RenderedSpan {
+                    text,
+                    style: span.style,
+                }),
+            }
         }
 
-        Ok(TextSpan {
-            text: TextType::Variable(it.take_while(|c| *c != '}').collect()),
-            color: *current_color,
-        }.into())
+        Ok(rendered)
+    }
+}
+
+impl VariableLookup for BTreeMap<String, u16> {
+    fn lookup(&self, name: &str) -> Option<u16> {
+        self.get(name).copied()
+    }
+}
+
+impl VariableLookup for HashMap<String, u16> {
+    fn lookup(&self, name: &str) -> Option<u16> {
+        self.get(name).copied()
     }
 }
 
@@ -164,92 +465,101 @@ impl FromStr for Text {
             true
         };
 
-        let mut spans = Vec::new();
-        let mut last = String::new();
-        let mut chars = s.chars();
-        let mut current_color = Foreground::Regular;
-
-        while let Some(chr) = chars.next() {
-            let span_opt = match chr {
-                '$' => parse_variable(
-                    &mut chars,
-                    &mut last,
-                    &mut current_color,
-                )?,
-                '{' => parse_braced_variable(
-                    &mut chars,
-                    &mut last,
-                    &mut current_color,
-                )?,
-                '\\' => {
-                    let prev = current_color;
-                    let color = parse_color(
-                        &mut chars,
-                        &mut last,
-                        &mut current_color,
-                    );
-                    if color.is_ok() && !last.is_empty() {
-                        spans.push(TextSpan {
-                            text: TextType::Plain(last.clone()),
-                            color: prev,
-                        });
-                        last.clear();
-
-                        None
-                    } else {
-                        color?
-                    }
-                }
+        Ok(Self::Spans {
+            spans: Parser::new(s).parse()?,
+            click_to_advance,
+        })
+    }
+}
 
-                _ => {
-                    last.push(chr);
-                    continue;
-                }
-            };
+/// Prints text in the NovelDS script syntax, styles are
+/// emitted as escape sequences
+impl fmt::Display for Text {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (spans, click_to_advance) = match self {
+            Self::BlankLine { click_to_advance } => {
+                return f.write_str(if *click_to_advance {
+                    "!"
+                } else {
+                    "~"
+                })
+            }
+            Self::Spans {
+                spans,
+                click_to_advance,
+            } => (spans, *click_to_advance),
+        };
 
-            if let Some(span) = span_opt {
-                if !last.is_empty() {
-                    spans.push(TextSpan {
-                        text: TextType::Plain(last.clone()),
-                        color: current_color,
-                    });
-                    last.clear();
-                }
+        let body = SpansDisplay(spans).to_string();
+        if !click_to_advance {
+            f.write_str("@")?;
+        } else if body.is_empty()
+            || body.starts_with('@')
+            || body == "~"
+            || body == "!"
+        {
+            // Text which is empty or reads as the marker is
+            // prefixed by a reset which changes nothing
+            write_style(f, &Style::default(), &Style::default())?;
+        }
+
+        f.write_str(&body)
+    }
+}
+
+/// Spans of the text without the markers
+struct SpansDisplay<'a>(&'a [TextSpan]);
 
-                spans.push(span);
+impl fmt::Display for SpansDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut current_style = Style::default();
+        for span in self.0 {
+            if span.style != current_style {
+                write_style(f, &current_style, &span.style)?;
+                current_style = span.style;
             }
-        }
 
-        if !last.is_empty() {
-            spans.push(TextSpan {
-                text: TextType::Plain(last),
-                color: current_color,
-            });
+            match &span.text {
+                TextType::Plain(text) => write_escaped(f, text)?,
+                TextType::Variable(name) => write!(f, "{{${name}}}")?,
+            }
         }
 
-        Ok(Self::Spans {
-            spans,
-            click_to_advance,
-        })
+        Ok(())
     }
 }
 
-impl TryFrom<u16> for Foreground {
-    type Error = TextParseError;
+/// Write plain text so it is parsed back as is
+fn write_escaped(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    for chr in text.chars() {
+        match chr {
+            '$' | '{' | '\\' => write!(f, "\\{chr}")?,
+            '\n' => f.write_str("\\n")?,
+            chr => write!(f, "{chr}")?,
+        }
+    }
 
-    fn try_from(value: u16) -> Result<Self, Self::Error> {
-        if matches!(value, 30..=37 | 39) {
-            union U {
-                i: u16,
-                e: Foreground,
-            }
+    Ok(())
+}
 
-            // SAFETY: this is safe since `Foreground`
-            // is declared as repr(u16) and integer range
-            // we already checked above
-            Ok(unsafe { U { i: value }.e })
-        } else {
-            Err(TextParseError::InvalidColor)
+/// Write escape sequence which switches from the `current`
+/// to the `next` style
+fn write_style(
+    f: &mut fmt::Formatter<'_>,
+    current: &Style,
+    next: &Style,
+) -> fmt::Result {
+    let mut parameters = next.parameters();
+    if parameters.is_empty() || current.needs_reset(next) {
+        parameters.insert(0, 0);
+    }
+
+    f.write_str(ESCAPE)?;
+    for (index, parameter) in parameters.iter().enumerate() {
+        if index != 0 {
+            f.write_str(";")?;
         }
+        write!(f, "{parameter}")?;
     }
+    f.write_str("m")
 }
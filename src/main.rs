@@ -1,3 +1,22 @@
-fn main() {
-    println!("Hello, world!");
+use std::{
+    env,
+    io,
+    process::ExitCode,
+};
+
+fn main() -> ExitCode {
+    let args: Vec<String> = env::args().skip(1).collect();
+
+    match vrs::cli::run(
+        &args,
+        &mut io::stdin().lock(),
+        &mut io::stdout().lock(),
+    ) {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("{e}");
+            ExitCode::from(2)
+        }
+    }
 }